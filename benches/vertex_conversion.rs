@@ -0,0 +1,45 @@
+//! Benchmarks the `DrawVert` to FVF vertex conversion with synthetic draw
+//! lists of typical UI sizes.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use imgui::DrawVert;
+
+#[allow(dead_code)]
+#[path = "../src/vertex.rs"]
+mod vertex;
+
+use vertex::CustomVertex;
+
+/// Builds a draw list worth of quads, as emitted for text and widget frames.
+fn synthetic_draw_list(quads: usize) -> Vec<DrawVert> {
+    (0..quads)
+        .flat_map(|i| {
+            let (x, y) = ((i % 64) as f32 * 12.0, (i / 64) as f32 * 16.0);
+            let col = [(i % 256) as u8, 0x80, 0xFF - (i % 256) as u8, 0xFF];
+            [[0.0, 0.0], [12.0, 0.0], [12.0, 16.0], [0.0, 16.0]].map(|[dx, dy]| DrawVert {
+                pos: [x + dx, y + dy],
+                uv: [dx / 1024.0, dy / 1024.0],
+                col,
+            })
+        })
+        .collect()
+}
+
+fn bench_conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert_vertices");
+    for quads in [1_000, 10_000, 50_000] {
+        let src = synthetic_draw_list(quads);
+        let mut dst = vec![CustomVertex::default(); src.len()];
+        group.throughput(Throughput::Elements(src.len() as u64));
+        group.bench_with_input(BenchmarkId::new("scalar", src.len()), &src, |b, src| {
+            b.iter(|| vertex::convert_vertices_scalar(black_box(src), black_box(&mut dst)))
+        });
+        group.bench_with_input(BenchmarkId::new("simd", src.len()), &src, |b, src| {
+            b.iter(|| vertex::convert_vertices(black_box(src), black_box(&mut dst)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_conversion);
+criterion_main!(benches);
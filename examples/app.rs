@@ -0,0 +1,25 @@
+#![cfg_attr(not(windows), allow(unused))]
+use imgui_dx9_renderer::app::{self, AppConfig};
+
+#[cfg(not(windows))]
+fn main() {}
+
+#[cfg(windows)]
+fn main() {
+    let config = AppConfig {
+        title: String::from("imgui_dx9_renderer app example"),
+        msaa_samples: 4,
+        ..AppConfig::default()
+    };
+    let mut counter = 0;
+    app::run(config, |ui| {
+        ui.window("Hello world").size([300.0, 100.0], imgui::Condition::FirstUseEver).build(|| {
+            if ui.button("Click me") {
+                counter += 1;
+            }
+            ui.text(format!("Clicked {} times", counter));
+        });
+        ui.show_demo_window(&mut true);
+    })
+    .unwrap();
+}
@@ -1,3 +1,4 @@
+#![cfg_attr(not(windows), allow(unused))]
 use std::{ptr, time::Instant};
 
 use imgui::{FontConfig, FontSource};
@@ -58,6 +59,10 @@ unsafe fn set_up_dx_context(hwnd: HWND) -> (IDirect3D9, IDirect3DDevice9) {
     }
 }
 
+#[cfg(not(windows))]
+fn main() {}
+
+#[cfg(windows)]
 fn main() {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
@@ -121,6 +126,7 @@ fn main() {
             ui.show_demo_window(&mut true);
             platform.prepare_render(ui, &window);
             renderer.render(imgui.render()).unwrap();
+            renderer.end_frame();
             unsafe {
                 device.EndScene().unwrap();
                 device.Present(ptr::null_mut(), ptr::null_mut(), None, ptr::null_mut()).unwrap();
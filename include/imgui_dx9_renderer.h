@@ -0,0 +1,107 @@
+/*
+ * C ABI of imgui-dx9-renderer, available with its `capi` feature.
+ *
+ * Generated from src/capi.rs with
+ * `cbindgen --config cbindgen.toml --output include/imgui_dx9_renderer.h`,
+ * do not edit by hand. Fallible functions return an HRESULT.
+ */
+
+#ifndef IMGUI_DX9_RENDERER_H
+#define IMGUI_DX9_RENDERER_H
+
+#include <stdint.h>
+
+struct ImDrawData;
+struct ImFontAtlas;
+
+typedef struct ImGuiDx9Renderer ImGuiDx9Renderer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * Creates a renderer for the `IDirect3DDevice9` `device`, adding a
+ * reference to it.
+ *
+ * The font texture is built from `fonts` and its id stored in the atlas'
+ * `TexID`. Returns null if either pointer is null or creating the device
+ * objects fails.
+ *
+ * # Safety
+ *
+ * `device` and `fonts` must be null or valid.
+ */
+ImGuiDx9Renderer *imgui_dx9_renderer_create(void *device, struct ImFontAtlas *fonts);
+
+/*
+ * Renders `draw_data` into the currently bound render target, has to be
+ * called between `BeginScene` and `EndScene`.
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`], `draw_data` must be null or valid.
+ */
+int32_t imgui_dx9_renderer_render(ImGuiDx9Renderer *renderer, const struct ImDrawData *draw_data);
+
+/*
+ * Registers the `IDirect3DBaseTexture9` `texture`, adding a reference to it,
+ * and writes the `ImTextureID` to draw it with into `id`.
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`], `texture` and `id` must be null or valid.
+ */
+int32_t imgui_dx9_renderer_register_texture(ImGuiDx9Renderer *renderer, void *texture, void **id);
+
+/*
+ * Removes the texture registered under `id`, releasing the renderer's
+ * reference to it. Unknown ids are ignored.
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`].
+ */
+void imgui_dx9_renderer_unregister_texture(ImGuiDx9Renderer *renderer, void *id);
+
+/*
+ * Releases the renderer's `D3DPOOL_DEFAULT` resources before the device is
+ * reset, see [`Renderer::invalidate_device_objects`].
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`].
+ */
+void imgui_dx9_renderer_invalidate(ImGuiDx9Renderer *renderer);
+
+/*
+ * Recreates the resources released by [`imgui_dx9_renderer_invalidate`]
+ * after the device has been reset, rebuilding the font texture from `fonts`.
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`], `fonts` must be null or valid.
+ */
+int32_t imgui_dx9_renderer_restore(ImGuiDx9Renderer *renderer, struct ImFontAtlas *fonts);
+
+/*
+ * Destroys `renderer`, releasing its references to the device and all
+ * registered textures. Null is ignored.
+ *
+ * # Safety
+ *
+ * `renderer` must be null or a renderer returned by
+ * [`imgui_dx9_renderer_create`] that has not been destroyed yet.
+ */
+void imgui_dx9_renderer_destroy(ImGuiDx9Renderer *renderer);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* IMGUI_DX9_RENDERER_H */
@@ -0,0 +1,360 @@
+//! A ready to use application runner, owning the window, the device and the
+//! renderer so that only the UI itself is left to write.
+//!
+//! ```no_run
+//! # #[cfg(windows)]
+//! imgui_dx9_renderer::app::run(Default::default(), |ui| {
+//!     ui.show_demo_window(&mut true);
+//! })
+//! .unwrap();
+//! ```
+
+use std::mem;
+
+use windows::Win32::Foundation::{BOOL, HWND};
+use windows::Win32::Graphics::Direct3D9::{
+    D3DADAPTER_DEFAULT, D3DFMT_X8R8G8B8, D3DMULTISAMPLE_NONE, D3DMULTISAMPLE_TYPE,
+    D3DPRESENT_INTERVAL_IMMEDIATE, D3DPRESENT_INTERVAL_ONE, D3DPRESENT_PARAMETERS,
+    D3DSWAPEFFECT_DISCARD,
+};
+
+/// The configuration of an application started with [`run`].
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    /// The title of the window.
+    pub title: String,
+    /// The initial size of the window's client area in logical pixels.
+    pub size: [f64; 2],
+    /// The ordinal of the adapter to create the device on.
+    pub adapter: u32,
+    /// Whether presenting waits for the vertical blank.
+    pub vsync: bool,
+    /// The number of samples per pixel for multisample antialiasing, values
+    /// below 2 disable it. Should the adapter not support the given count
+    /// multisampling is disabled as well.
+    pub msaa_samples: u32,
+    /// The color the back buffer is cleared to every frame, as RGBA.
+    pub clear_color: [f32; 4],
+    /// The size of imgui's default font in logical pixels, it is rasterized
+    /// at the window's scale factor.
+    pub font_size: f32,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            title: String::from("imgui-dx9-renderer"),
+            size: [1024.0, 768.0],
+            adapter: D3DADAPTER_DEFAULT,
+            vsync: true,
+            msaa_samples: 0,
+            clear_color: [0.45, 0.55, 0.60, 1.00],
+            font_size: 13.0,
+        }
+    }
+}
+
+fn multisample_type(samples: u32) -> D3DMULTISAMPLE_TYPE {
+    if samples < 2 {
+        D3DMULTISAMPLE_NONE
+    } else {
+        D3DMULTISAMPLE_TYPE(samples.min(16) as i32)
+    }
+}
+
+/// The present parameters for a back buffer of `size` physical pixels, which
+/// is kept at least 1x1 as minimized windows report an empty client area.
+fn present_parameters(
+    config: &AppConfig,
+    hwnd: HWND,
+    size: [u32; 2],
+    multisample: D3DMULTISAMPLE_TYPE,
+) -> D3DPRESENT_PARAMETERS {
+    let interval =
+        if config.vsync { D3DPRESENT_INTERVAL_ONE } else { D3DPRESENT_INTERVAL_IMMEDIATE };
+    D3DPRESENT_PARAMETERS {
+        BackBufferWidth: size[0].max(1),
+        BackBufferHeight: size[1].max(1),
+        BackBufferFormat: D3DFMT_X8R8G8B8,
+        BackBufferCount: 1,
+        MultiSampleType: multisample,
+        SwapEffect: D3DSWAPEFFECT_DISCARD,
+        hDeviceWindow: hwnd,
+        Windowed: BOOL(1),
+        EnableAutoDepthStencil: BOOL(0),
+        PresentationInterval: interval as u32,
+        // SAFETY: all remaining fields are plain integers
+        ..unsafe { mem::zeroed() }
+    }
+}
+
+/// Converts an RGBA color into a `D3DCOLOR`.
+fn d3d_color(rgba: [f32; 4]) -> u32 {
+    let [r, g, b, a] = rgba.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u32);
+    (a << 24) | (r << 16) | (g << 8) | b
+}
+
+#[cfg(windows)]
+pub use self::runner::run;
+
+#[cfg(windows)]
+mod runner {
+    use std::time::{Duration, Instant};
+    use std::{ptr, thread};
+
+    use imgui::{Context, FontConfig, FontSource, Ui};
+    use imgui_winit_support::{HiDpiMode, WinitPlatform};
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+    use windows::core::{Error, HRESULT};
+    use windows::Win32::Foundation::{BOOL, E_FAIL, HWND};
+    use windows::Win32::Graphics::Direct3D9::{
+        Direct3DCreate9, IDirect3DDevice9, D3DCREATE_HARDWARE_VERTEXPROCESSING,
+        D3DCREATE_SOFTWARE_VERTEXPROCESSING, D3DDEVTYPE_HAL, D3DFMT_X8R8G8B8, D3DMULTISAMPLE_NONE,
+        D3DPRESENT_PARAMETERS, D3D_SDK_VERSION,
+    };
+    use windows::Win32::System::SystemServices::D3DCLEAR_TARGET;
+    use winit::dpi::LogicalSize;
+    use winit::event::{Event, WindowEvent};
+    use winit::event_loop::{ControlFlow, EventLoop};
+    use winit::platform::run_return::EventLoopExtRunReturn;
+    use winit::window::{Window, WindowBuilder};
+
+    use super::{d3d_color, multisample_type, present_parameters, AppConfig};
+    use crate::{Renderer, Result};
+
+    // not part of the windows crate's Direct3D9 bindings
+    const D3DERR_DEVICELOST: HRESULT = HRESULT(0x8876_0868_u32 as i32);
+    const D3DERR_DEVICENOTRESET: HRESULT = HRESULT(0x8876_0869_u32 as i32);
+
+    /// How long to wait before checking whether a lost device can be reset.
+    const DEVICE_LOST_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Opens a window and calls `frame` to build the UI of every frame until
+    /// the window is closed.
+    ///
+    /// Resizing the window resets the device to the new size and a lost
+    /// device is recreated as soon as possible, frames are skipped in the
+    /// meantime. Errors while setting up or rendering end the application.
+    pub fn run<F>(config: AppConfig, mut frame: F) -> Result<()>
+    where
+        F: FnMut(&mut Ui),
+    {
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_title(&config.title)
+            .with_inner_size(LogicalSize::new(config.size[0], config.size[1]))
+            .build(&event_loop)
+            .map_err(|e| Error::new(E_FAIL, e.to_string().into()))?;
+
+        let mut imgui = Context::create();
+        let mut platform = WinitPlatform::init(&mut imgui);
+        platform.attach_window(imgui.io_mut(), &window, HiDpiMode::Default);
+        let hidpi_factor = platform.hidpi_factor() as f32;
+        imgui.fonts().add_font(&[FontSource::DefaultFontData {
+            config: Some(FontConfig {
+                size_pixels: config.font_size * hidpi_factor,
+                ..FontConfig::default()
+            }),
+        }]);
+        imgui.io_mut().font_global_scale = 1.0 / hidpi_factor;
+
+        let mut gfx = unsafe { Graphics::new(&config, &window, &mut imgui)? };
+        let mut last_frame = Instant::now();
+        let mut result = Ok(());
+        event_loop.run_return(|event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+            platform.handle_event(imgui.io_mut(), &window, &event);
+            match event {
+                Event::NewEvents(_) => {
+                    let now = Instant::now();
+                    imgui.io_mut().update_delta_time(now - last_frame);
+                    last_frame = now;
+                },
+                Event::MainEventsCleared => {
+                    if let Err(e) = platform.prepare_frame(imgui.io_mut(), &window) {
+                        result = Err(Error::new(E_FAIL, e.to_string().into()));
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    window.request_redraw();
+                },
+                Event::RedrawRequested(_) => {
+                    let drawn = unsafe {
+                        gfx.draw(&mut imgui, |ui| {
+                            frame(ui);
+                            platform.prepare_render(ui, &window);
+                        })
+                    };
+                    if let Err(e) = drawn {
+                        result = Err(e);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                },
+                Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                    gfx.resize([size.width, size.height]);
+                },
+                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                    *control_flow = ControlFlow::Exit;
+                },
+                _ => (),
+            }
+        });
+        result
+    }
+
+    struct Graphics {
+        device: IDirect3DDevice9,
+        present_params: D3DPRESENT_PARAMETERS,
+        renderer: Renderer,
+        clear_color: u32,
+        needs_reset: bool,
+    }
+
+    impl Graphics {
+        unsafe fn new(config: &AppConfig, window: &Window, imgui: &mut Context) -> Result<Self> {
+            let hwnd = match window.raw_window_handle() {
+                RawWindowHandle::Win32(handle) => HWND(handle.hwnd as isize),
+                _ => unreachable!(),
+            };
+            let d3d = Direct3DCreate9(D3D_SDK_VERSION).ok_or_else(|| Error::from(E_FAIL))?;
+            let mut multisample = multisample_type(config.msaa_samples);
+            if multisample != D3DMULTISAMPLE_NONE
+                && d3d
+                    .CheckDeviceMultiSampleType(
+                        config.adapter,
+                        D3DDEVTYPE_HAL,
+                        D3DFMT_X8R8G8B8,
+                        BOOL(1),
+                        multisample,
+                        ptr::null_mut(),
+                    )
+                    .is_err()
+            {
+                multisample = D3DMULTISAMPLE_NONE;
+            }
+            let size = window.inner_size();
+            let mut present_params =
+                present_parameters(config, hwnd, [size.width, size.height], multisample);
+
+            // prefer hardware vertex processing, falling back to software
+            let mut device = None;
+            let mut created = Ok(());
+            for behavior in
+                [D3DCREATE_HARDWARE_VERTEXPROCESSING, D3DCREATE_SOFTWARE_VERTEXPROCESSING]
+            {
+                created = d3d.CreateDevice(
+                    config.adapter,
+                    D3DDEVTYPE_HAL,
+                    hwnd,
+                    behavior as u32,
+                    &mut present_params,
+                    &mut device,
+                );
+                if created.is_ok() {
+                    break;
+                }
+            }
+            created?;
+            let device = device.unwrap();
+            let renderer = Renderer::new(imgui, device.clone())?;
+            Ok(Graphics {
+                device,
+                present_params,
+                renderer,
+                clear_color: d3d_color(config.clear_color),
+                needs_reset: false,
+            })
+        }
+
+        fn resize(&mut self, size: [u32; 2]) {
+            // minimized windows keep their back buffer
+            if size[0] == 0 || size[1] == 0 {
+                return;
+            }
+            if size != [self.present_params.BackBufferWidth, self.present_params.BackBufferHeight] {
+                self.present_params.BackBufferWidth = size[0];
+                self.present_params.BackBufferHeight = size[1];
+                self.needs_reset = true;
+            }
+        }
+
+        /// Makes sure the device is usable, resetting it if necessary. Returns
+        /// `false` if the device is lost and can not be reset yet.
+        unsafe fn restore(&mut self, imgui: &mut Context) -> Result<bool> {
+            match self.device.TestCooperativeLevel() {
+                Ok(()) if !self.needs_reset => return Ok(true),
+                Ok(()) => (),
+                Err(e) if e.code() == D3DERR_DEVICENOTRESET => (),
+                Err(e) if e.code() == D3DERR_DEVICELOST => return Ok(false),
+                Err(e) => return Err(e),
+            }
+            self.renderer.invalidate_device_objects();
+            match self.device.Reset(&mut self.present_params) {
+                Ok(()) => (),
+                Err(e) if e.code() == D3DERR_DEVICELOST => return Ok(false),
+                Err(e) => return Err(e),
+            }
+            self.needs_reset = false;
+            self.renderer.create_device_objects(imgui)?;
+            Ok(true)
+        }
+
+        unsafe fn draw(
+            &mut self,
+            imgui: &mut Context,
+            build_ui: impl FnOnce(&mut Ui),
+        ) -> Result<()> {
+            if !self.restore(imgui)? {
+                thread::sleep(DEVICE_LOST_POLL_INTERVAL);
+                return Ok(());
+            }
+            self.device.Clear(0, ptr::null(), D3DCLEAR_TARGET as u32, self.clear_color, 1.0, 0)?;
+            self.device.BeginScene()?;
+            build_ui(imgui.new_frame());
+            let rendered = self.renderer.render(imgui.render());
+            self.renderer.end_frame();
+            self.device.EndScene()?;
+            rendered?;
+            match self.device.Present(ptr::null(), ptr::null(), None, ptr::null()) {
+                // picked up by the next `restore`
+                Err(e) if e.code() == D3DERR_DEVICELOST => Ok(()),
+                presented => presented,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn present_parameters_follow_config() {
+        let config = AppConfig { vsync: false, ..AppConfig::default() };
+        let params = present_parameters(&config, HWND(0), [800, 600], D3DMULTISAMPLE_TYPE(4));
+        assert_eq!([params.BackBufferWidth, params.BackBufferHeight], [800, 600]);
+        assert_eq!(params.PresentationInterval, D3DPRESENT_INTERVAL_IMMEDIATE as u32);
+        assert_eq!(params.MultiSampleType, D3DMULTISAMPLE_TYPE(4));
+        // multisampling requires the discard swap effect
+        assert_eq!(params.SwapEffect, D3DSWAPEFFECT_DISCARD);
+
+        let params =
+            present_parameters(&AppConfig::default(), HWND(0), [0, 0], D3DMULTISAMPLE_NONE);
+        assert_eq!([params.BackBufferWidth, params.BackBufferHeight], [1, 1]);
+        assert_eq!(params.PresentationInterval, D3DPRESENT_INTERVAL_ONE as u32);
+    }
+
+    #[test]
+    fn multisample_type_from_samples() {
+        assert_eq!(multisample_type(0), D3DMULTISAMPLE_NONE);
+        assert_eq!(multisample_type(1), D3DMULTISAMPLE_NONE);
+        assert_eq!(multisample_type(8), D3DMULTISAMPLE_TYPE(8));
+        assert_eq!(multisample_type(64), D3DMULTISAMPLE_TYPE(16));
+    }
+
+    #[test]
+    fn converts_clear_color() {
+        assert_eq!(d3d_color([1.0, 0.0, 0.0, 1.0]), 0xFFFF_0000);
+        assert_eq!(d3d_color([0.0, 0.5, 1.0, 0.0]), 0x0000_80FF);
+        assert_eq!(d3d_color([2.0, -1.0, 0.0, 1.0]), 0xFFFF_0000);
+    }
+}
@@ -0,0 +1,179 @@
+//! C ABI for hosts driving imgui from C or C++, for example through cimgui.
+//!
+//! Build the library with the `capi` feature as a `cdylib` or `staticlib`,
+//! for example with
+//! `cargo rustc --release --features capi --crate-type cdylib`, and include
+//! `include/imgui_dx9_renderer.h`, which is generated with cbindgen.
+//!
+//! No imgui-rs [`Context`](imgui::Context) is involved, the functions only
+//! read the `ImDrawData` and `ImFontAtlas` of the host, which therefore have
+//! to stem from the imgui version imgui-rs is built against. Fallible
+//! functions return an `HRESULT`, passing a null pointer where an object is
+//! expected yields `DXGI_ERROR_INVALID_CALL`. Panics do not unwind into the
+//! host but are reported as `E_FAIL`, or a null renderer.
+
+use std::ffi::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use imgui::internal::RawCast;
+use imgui::{sys, FontAtlas, TextureId};
+use windows::core::{Interface, HRESULT};
+use windows::Win32::Foundation::{E_FAIL, S_OK};
+use windows::Win32::Graphics::Direct3D9::{IDirect3DBaseTexture9, IDirect3DDevice9};
+use windows::Win32::Graphics::Dxgi::DXGI_ERROR_INVALID_CALL;
+
+use crate::{Renderer, Result};
+
+/// Runs `f`, returning `fallback` should it panic, as unwinding into the host
+/// is undefined behavior.
+fn catch<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+fn hresult(result: Result<()>) -> HRESULT {
+    match result {
+        Ok(()) => S_OK,
+        Err(e) => e.code(),
+    }
+}
+
+/// Creates a renderer for the `IDirect3DDevice9` `device`, adding a
+/// reference to it.
+///
+/// The font texture is built from `fonts` and its id stored in the atlas'
+/// `TexID`. Returns null if either pointer is null or creating the device
+/// objects fails.
+///
+/// # Safety
+///
+/// `device` and `fonts` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_create(
+    device: *mut c_void,
+    fonts: *mut sys::ImFontAtlas,
+) -> *mut Renderer {
+    catch(ptr::null_mut(), || {
+        let device = match IDirect3DDevice9::from_raw_borrowed(&device) {
+            Some(device) if !fonts.is_null() => device.clone(),
+            _ => return ptr::null_mut(),
+        };
+        match Renderer::with_font_atlases(device, [FontAtlas::from_raw_mut(&mut *fonts)]) {
+            Ok(renderer) => Box::into_raw(Box::new(renderer)),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Renders `draw_data` into the currently bound render target, has to be
+/// called between `BeginScene` and `EndScene`.
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`], `draw_data` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_render(
+    renderer: *mut Renderer,
+    draw_data: *const sys::ImDrawData,
+) -> HRESULT {
+    catch(E_FAIL, || match renderer.as_mut() {
+        Some(renderer) => hresult(renderer.render_raw(draw_data)),
+        None => DXGI_ERROR_INVALID_CALL,
+    })
+}
+
+/// Registers the `IDirect3DBaseTexture9` `texture`, adding a reference to it,
+/// and writes the `ImTextureID` to draw it with into `id`.
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`], `texture` and `id` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_register_texture(
+    renderer: *mut Renderer,
+    texture: *mut c_void,
+    id: *mut *mut c_void,
+) -> HRESULT {
+    catch(E_FAIL, || {
+        match (renderer.as_mut(), IDirect3DBaseTexture9::from_raw_borrowed(&texture), id.as_mut()) {
+            (Some(renderer), Some(texture), Some(id)) => {
+                *id = renderer.textures_mut().insert(texture.clone()).id() as *mut c_void;
+                S_OK
+            },
+            _ => DXGI_ERROR_INVALID_CALL,
+        }
+    })
+}
+
+/// Removes the texture registered under `id`, releasing the renderer's
+/// reference to it. Unknown ids are ignored.
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`].
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_unregister_texture(
+    renderer: *mut Renderer,
+    id: *mut c_void,
+) {
+    catch((), || {
+        if let Some(renderer) = renderer.as_mut() {
+            renderer.textures_mut().remove(TextureId::from(id));
+        }
+    })
+}
+
+/// Releases the renderer's `D3DPOOL_DEFAULT` resources before the device is
+/// reset, see [`Renderer::invalidate_device_objects`].
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`].
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_invalidate(renderer: *mut Renderer) {
+    catch((), || {
+        if let Some(renderer) = renderer.as_mut() {
+            renderer.invalidate_device_objects();
+        }
+    })
+}
+
+/// Recreates the resources released by [`imgui_dx9_renderer_invalidate`]
+/// after the device has been reset, rebuilding the font texture from `fonts`.
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`], `fonts` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_restore(
+    renderer: *mut Renderer,
+    fonts: *mut sys::ImFontAtlas,
+) -> HRESULT {
+    catch(E_FAIL, || match (renderer.as_mut(), fonts.as_mut()) {
+        (Some(renderer), Some(fonts)) => {
+            hresult(renderer.create_device_objects_with_fonts([FontAtlas::from_raw_mut(fonts)]))
+        },
+        _ => DXGI_ERROR_INVALID_CALL,
+    })
+}
+
+/// Destroys `renderer`, releasing its references to the device and all
+/// registered textures. Null is ignored.
+///
+/// # Safety
+///
+/// `renderer` must be null or a renderer returned by
+/// [`imgui_dx9_renderer_create`] that has not been destroyed yet.
+#[no_mangle]
+pub unsafe extern "C" fn imgui_dx9_renderer_destroy(renderer: *mut Renderer) {
+    catch((), || {
+        if !renderer.is_null() {
+            drop(Box::from_raw(renderer));
+        }
+    })
+}
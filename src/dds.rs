@@ -0,0 +1,285 @@
+//! Parsing of DDS files into the surfaces of a Direct3D 9 texture.
+
+use std::fmt;
+
+use crate::dxt::DxtFormat;
+use crate::pixels::{self, PixelFormat};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 124;
+const DATA_OFFSET: usize = MAGIC.len() + HEADER_SIZE;
+/// The largest texture size of Direct3D 9 hardware, which keeps the surface
+/// sizes from overflowing on 32 bit targets.
+const MAX_SIZE: u32 = 16384;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+/// The pixel formats of DDS files that can be loaded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DdsFormat {
+    Dxt(DxtFormat),
+    /// 32 bit BGRA, files storing RGBA are converted into this.
+    A8R8G8B8,
+    /// 32 bit BGR with an unused fourth byte.
+    X8R8G8B8,
+}
+
+impl DdsFormat {
+    /// The size of a `width` x `height` surface as its row pitch in bytes and
+    /// number of rows, where a row of a compressed format is a row of blocks.
+    fn surface_size(self, width: u32, height: u32) -> (usize, usize) {
+        PixelFormat::from(self).rows(width as usize, height as usize)
+    }
+}
+
+impl From<DdsFormat> for PixelFormat {
+    fn from(format: DdsFormat) -> Self {
+        match format {
+            DdsFormat::Dxt(format) => PixelFormat::Dxt(format),
+            DdsFormat::A8R8G8B8 => PixelFormat::A8R8G8B8,
+            DdsFormat::X8R8G8B8 => PixelFormat::X8R8G8B8,
+        }
+    }
+}
+
+/// One mip level of one face.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DdsSurface {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// Bytes per row, or per row of blocks for compressed formats.
+    pub(crate) pitch: usize,
+    pub(crate) data: Vec<u8>,
+}
+
+/// A parsed DDS file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DdsTexture {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: DdsFormat,
+    pub(crate) mip_levels: u32,
+    /// Whether this is a cube map with all six faces.
+    pub(crate) cube: bool,
+    /// The surfaces ordered by face, then mip level, largest first. Faces
+    /// are ordered +X, -X, +Y, -Y, +Z, -Z as in `D3DCUBEMAP_FACES`.
+    pub(crate) surfaces: Vec<DdsSurface>,
+}
+
+/// Why a DDS file could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DdsError {
+    /// The file does not start with a DDS header.
+    NotDds,
+    /// The file ends before all surfaces.
+    Truncated,
+    /// The file uses a feature or format that is not supported.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for DdsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DdsError::NotDds => f.write_str("not a DDS file"),
+            DdsError::Truncated => f.write_str("truncated DDS file"),
+            DdsError::Unsupported(what) => write!(f, "unsupported DDS file: {}", what),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Maps the pixel format of the header starting at `pf` to a [`DdsFormat`],
+/// returning whether red and blue have to be swapped as well.
+fn pixel_format(bytes: &[u8], pf: usize) -> Result<(DdsFormat, bool), DdsError> {
+    let flags = read_u32(bytes, pf + 4);
+    if flags & DDPF_FOURCC != 0 {
+        return match &bytes[pf + 8..pf + 12] {
+            b"DXT1" => Ok((DdsFormat::Dxt(DxtFormat::Dxt1), false)),
+            b"DXT3" => Ok((DdsFormat::Dxt(DxtFormat::Dxt3), false)),
+            b"DXT5" => Ok((DdsFormat::Dxt(DxtFormat::Dxt5), false)),
+            b"DX10" => Err(DdsError::Unsupported("DX10 header")),
+            _ => Err(DdsError::Unsupported("compressed format")),
+        };
+    }
+    let bit_count = read_u32(bytes, pf + 12);
+    if flags & DDPF_RGB == 0 || bit_count != 32 {
+        return Err(DdsError::Unsupported("uncompressed format"));
+    }
+    let masks = [pf + 16, pf + 20, pf + 24].map(|offset| read_u32(bytes, offset));
+    let alpha = flags & DDPF_ALPHAPIXELS != 0;
+    let format = if alpha { DdsFormat::A8R8G8B8 } else { DdsFormat::X8R8G8B8 };
+    match masks {
+        [0xFF_0000, 0xFF00, 0xFF] => Ok((format, false)),
+        [0xFF, 0xFF00, 0xFF_0000] => Ok((format, true)),
+        _ => Err(DdsError::Unsupported("channel masks")),
+    }
+}
+
+/// Parses a DDS file with a 2D texture or a complete cube map, in DXT1, DXT3,
+/// DXT5 or 32 bit RGB(A) format.
+pub(crate) fn parse(bytes: &[u8]) -> Result<DdsTexture, DdsError> {
+    if bytes.len() < DATA_OFFSET
+        || &bytes[..4] != MAGIC
+        || read_u32(bytes, 4) as usize != HEADER_SIZE
+    {
+        return Err(DdsError::NotDds);
+    }
+    let flags = read_u32(bytes, 8);
+    let height = read_u32(bytes, 12);
+    let width = read_u32(bytes, 16);
+    let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 { read_u32(bytes, 28).max(1) } else { 1 };
+    let caps2 = read_u32(bytes, 112);
+    let (format, swap_red_blue) = pixel_format(bytes, 76)?;
+
+    if width == 0 || height == 0 {
+        return Err(DdsError::Unsupported("empty texture"));
+    }
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(DdsError::Unsupported("texture size"));
+    }
+    // Direct3D 9 requires the top level of DXT textures to be whole blocks
+    if matches!(format, DdsFormat::Dxt(_))
+        && (!width.is_multiple_of(4) || !height.is_multiple_of(4))
+    {
+        return Err(DdsError::Unsupported("DXT size not a multiple of 4"));
+    }
+    if mip_levels > 32 || (width >> (mip_levels - 1)) == 0 && (height >> (mip_levels - 1)) == 0 {
+        return Err(DdsError::Unsupported("mip level count"));
+    }
+    if caps2 & DDSCAPS2_VOLUME != 0 {
+        return Err(DdsError::Unsupported("volume texture"));
+    }
+    let cube = caps2 & DDSCAPS2_CUBEMAP != 0;
+    if cube && (caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES || width != height) {
+        return Err(DdsError::Unsupported("partial cube map"));
+    }
+
+    let mut data = &bytes[DATA_OFFSET..];
+    let mut surfaces = Vec::new();
+    for _face in 0..if cube { 6 } else { 1 } {
+        for level in 0..mip_levels {
+            let (width, height) = ((width >> level).max(1), (height >> level).max(1));
+            let (pitch, rows) = format.surface_size(width, height);
+            let len = pitch.checked_mul(rows).filter(|&len| len <= data.len());
+            let (surface, rest) = data.split_at(len.ok_or(DdsError::Truncated)?);
+            let mut surface = surface.to_vec();
+            if swap_red_blue {
+                pixels::swap_red_blue(&mut surface);
+            }
+            surfaces.push(DdsSurface { width, height, pitch, data: surface });
+            data = rest;
+        }
+    }
+    Ok(DdsTexture { width, height, format, mip_levels, cube, surfaces })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dds/", $name))
+        };
+    }
+
+    fn sizes(texture: &DdsTexture) -> Vec<(u32, u32, usize, usize)> {
+        texture
+            .surfaces
+            .iter()
+            .map(|s| (s.width, s.height, s.pitch, s.data.len() / s.pitch))
+            .collect()
+    }
+
+    #[test]
+    fn parses_uncompressed_mip_chain() {
+        let bytes = fixture!("a8r8g8b8_mips.dds");
+        let texture = parse(bytes).unwrap();
+        assert_eq!(texture.format, DdsFormat::A8R8G8B8);
+        assert_eq!((texture.width, texture.height, texture.mip_levels), (4, 2, 3));
+        assert!(!texture.cube);
+        assert_eq!(sizes(&texture), [(4, 2, 16, 2), (2, 1, 8, 1), (1, 1, 4, 1)]);
+        let data: Vec<u8> = texture.surfaces.iter().flat_map(|s| s.data.clone()).collect();
+        assert_eq!(data, &bytes[DATA_OFFSET..]);
+    }
+
+    #[test]
+    fn converts_rgba_into_bgra() {
+        let texture = parse(fixture!("a8b8g8r8.dds")).unwrap();
+        assert_eq!(texture.format, DdsFormat::A8R8G8B8);
+        assert_eq!(texture.surfaces[0].data, [3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn parses_formats_without_alpha() {
+        let texture = parse(fixture!("x8r8g8b8.dds")).unwrap();
+        assert_eq!(texture.format, DdsFormat::X8R8G8B8);
+        assert_eq!(texture.surfaces[0].data, [9, 8, 7, 0]);
+    }
+
+    #[test]
+    fn parses_dxt1_mip_chain_down_to_single_blocks() {
+        let texture = parse(fixture!("dxt1_mips.dds")).unwrap();
+        assert_eq!(texture.format, DdsFormat::Dxt(DxtFormat::Dxt1));
+        assert_eq!(sizes(&texture), [(8, 8, 16, 2), (4, 4, 8, 1), (2, 2, 8, 1), (1, 1, 8, 1)]);
+    }
+
+    #[test]
+    fn parses_dxt3_blocks() {
+        let texture = parse(fixture!("dxt3.dds")).unwrap();
+        assert_eq!(texture.format, DdsFormat::Dxt(DxtFormat::Dxt3));
+        assert_eq!(sizes(&texture), [(8, 4, 32, 1)]);
+    }
+
+    #[test]
+    fn parses_dxt5_cube_map_faces() {
+        let bytes = fixture!("dxt5_cube.dds");
+        let texture = parse(bytes).unwrap();
+        assert_eq!(texture.format, DdsFormat::Dxt(DxtFormat::Dxt5));
+        assert!(texture.cube);
+        assert_eq!(texture.surfaces.len(), 12);
+        // the second face starts after both levels of the first
+        assert_eq!(texture.surfaces[2].data, &bytes[DATA_OFFSET + 32..DATA_OFFSET + 48]);
+        assert_eq!(sizes(&texture)[10..], [(4, 4, 16, 1), (2, 2, 16, 1)]);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert_eq!(parse(b"DDS "), Err(DdsError::NotDds));
+        assert_eq!(parse(&fixture!("dxt5_cube.dds")[4..]), Err(DdsError::NotDds));
+        let bytes = fixture!("dxt1_mips.dds");
+        assert_eq!(parse(&bytes[..bytes.len() - 1]), Err(DdsError::Truncated));
+        assert_eq!(parse(fixture!("dx10.dds")), Err(DdsError::Unsupported("DX10 header")));
+    }
+
+    fn with_size(bytes: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn rejects_partial_dxt_blocks() {
+        let bytes = with_size(fixture!("dxt3.dds"), 5, 3);
+        assert_eq!(parse(&bytes), Err(DdsError::Unsupported("DXT size not a multiple of 4")));
+    }
+
+    #[test]
+    fn rejects_oversized_textures() {
+        let bytes = fixture!("a8r8g8b8_mips.dds");
+        assert_eq!(parse(&with_size(bytes, MAX_SIZE, 2)), Err(DdsError::Truncated));
+        let oversized = Err(DdsError::Unsupported("texture size"));
+        assert_eq!(parse(&with_size(bytes, MAX_SIZE + 1, 2)), oversized);
+        assert_eq!(parse(&with_size(bytes, 4, u32::MAX)), oversized);
+    }
+}
@@ -0,0 +1,48 @@
+//! Decoding of BMP, JPEG, PNG and TGA files into texture data.
+
+use image::ImageResult;
+
+use crate::mipmap::MipLevel;
+
+/// Decodes an image file held in memory into RGBA pixels, guessing the format
+/// from the contents.
+pub(crate) fn decode_texture(bytes: &[u8]) -> ImageResult<MipLevel> {
+    let image = image::load_from_memory(bytes)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(MipLevel { width, height, data: image.into_raw() })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn encode(image: &RgbaImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn decodes_png_into_rgba() {
+        let image = RgbaImage::from_pixel(4, 2, Rgba([10, 20, 30, 40]));
+        let decoded = decode_texture(&encode(&image, ImageOutputFormat::Png)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (4, 2));
+        assert_eq!(decoded.data, image.into_raw());
+    }
+
+    #[test]
+    fn decodes_formats_without_alpha() {
+        let image = RgbaImage::from_pixel(2, 2, Rgba([200, 100, 50, 255]));
+        let decoded = decode_texture(&encode(&image, ImageOutputFormat::Bmp)).unwrap();
+        assert_eq!(&decoded.data[..4], [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn rejects_unknown_data() {
+        assert!(decode_texture(b"definitely not an image").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! CPU compression of RGBA pixels into DXT1 and DXT5 blocks and decompression
+//! of DXT1, DXT3 and DXT5 blocks.
+
+/// How much effort the DXT encoder spends on finding block endpoints.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CompressionQuality {
+    /// The extremes of the colors along their principal axis.
+    Fast,
+    /// [`Fast`](Self::Fast) endpoints refined once with least squares.
+    #[default]
+    Normal,
+    /// Endpoints refined with least squares until they stop improving, up to
+    /// four times.
+    High,
+}
+
+impl CompressionQuality {
+    fn refinements(self) -> usize {
+        match self {
+            CompressionQuality::Fast => 0,
+            CompressionQuality::Normal => 1,
+            CompressionQuality::High => 4,
+        }
+    }
+}
+
+/// The DXT formats of Direct3D 9.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DxtFormat {
+    /// Opaque colors, 8 bytes per block.
+    Dxt1,
+    /// Colors with explicit 4 bit alpha, 16 bytes per block, only decoded.
+    Dxt3,
+    /// Colors with interpolated alpha, 16 bytes per block.
+    Dxt5,
+}
+
+impl DxtFormat {
+    /// The length of a row of the blocks covering `width` pixels and the
+    /// number of such rows covering `height` pixels.
+    pub(crate) fn rows(self, width: usize, height: usize) -> (usize, usize) {
+        (width.div_ceil(4) * self.block_size(), height.div_ceil(4))
+    }
+
+    pub(crate) fn block_size(self) -> usize {
+        match self {
+            DxtFormat::Dxt1 => 8,
+            DxtFormat::Dxt3 | DxtFormat::Dxt5 => 16,
+        }
+    }
+}
+
+type Rgb = [f32; 3];
+
+fn to_565(c: Rgb) -> u16 {
+    let q = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    (q(c[0], 31.0) << 11) | (q(c[1], 63.0) << 5) | q(c[2], 31.0)
+}
+
+fn from_565(c: u16) -> [u8; 3] {
+    let (r, g, b) = ((c >> 11) as u8, ((c >> 5) & 0x3F) as u8, (c & 0x1F) as u8);
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// The four colors of a block in 4 color mode.
+fn palette(c0: u16, c1: u16) -> [[u8; 3]; 4] {
+    let (a, b) = (from_565(c0), from_565(c1));
+    let mix = |wa: u16, wb: u16| {
+        [0, 1, 2].map(|i| ((wa * u16::from(a[i]) + wb * u16::from(b[i])) / 3) as u8)
+    };
+    [a, b, mix(2, 1), mix(1, 2)]
+}
+
+fn distance(a: [u8; 3], b: &[u8]) -> u32 {
+    (0..3).map(|i| (i32::from(a[i]) - i32::from(b[i])).pow(2) as u32).sum()
+}
+
+/// Picks the closest palette entry for every pixel, returning the indices and
+/// the total squared error.
+fn fit_indices(pixels: &[[u8; 4]; 16], palette: &[[u8; 3]; 4]) -> ([u8; 16], u32) {
+    let mut indices = [0; 16];
+    let mut error = 0;
+    for (index, pixel) in indices.iter_mut().zip(pixels) {
+        let (best, best_error) =
+            (0..4).map(|i| (i, distance(palette[i], pixel))).min_by_key(|&(_, e)| e).unwrap();
+        *index = best as u8;
+        error += best_error;
+    }
+    (indices, error)
+}
+
+/// The extremes of `pixels` along their principal axis.
+fn principal_endpoints(pixels: &[[u8; 4]; 16]) -> (Rgb, Rgb) {
+    let colors = pixels.map(|p| [0, 1, 2].map(|i| f32::from(p[i])));
+    let mean = [0, 1, 2].map(|i| colors.iter().map(|c| c[i]).sum::<f32>() / 16.0);
+    let mut cov = [[0.0f32; 3]; 3];
+    for c in &colors {
+        let d = [0, 1, 2].map(|i| c[i] - mean[i]);
+        for (i, row) in cov.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v += d[i] * d[j];
+            }
+        }
+    }
+    // start with the covariance of the channel varying the most, as any
+    // fixed vector may be orthogonal to the principal axis
+    let mut axis = cov[(0..3).max_by(|&i, &j| cov[i][i].total_cmp(&cov[j][j])).unwrap()];
+    for _ in 0..8 {
+        let next = [0, 1, 2].map(|i| (0..3).map(|j| cov[i][j] * axis[j]).sum::<f32>());
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / len);
+    }
+    let project = |c: &Rgb| (0..3).map(|i| (c[i] - mean[i]) * axis[i]).sum::<f32>();
+    let (mut min, mut max) = (colors[0], colors[0]);
+    for c in &colors {
+        if project(c) < project(&min) {
+            min = *c;
+        }
+        if project(c) > project(&max) {
+            max = *c;
+        }
+    }
+    (max, min)
+}
+
+/// Solves for the endpoints that best reproduce `pixels` with the given
+/// indices, `None` if the indices do not determine them.
+fn least_squares_endpoints(pixels: &[[u8; 4]; 16], indices: &[u8; 16]) -> Option<(Rgb, Rgb)> {
+    const WEIGHTS: [f32; 4] = [1.0, 0.0, 2.0 / 3.0, 1.0 / 3.0];
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+    for (pixel, &index) in pixels.iter().zip(indices) {
+        let (a, b) = (WEIGHTS[index as usize], 1.0 - WEIGHTS[index as usize]);
+        aa += a * a;
+        ab += a * b;
+        bb += b * b;
+        for i in 0..3 {
+            ax[i] += a * f32::from(pixel[i]);
+            bx[i] += b * f32::from(pixel[i]);
+        }
+    }
+    let det = aa * bb - ab * ab;
+    if det.abs() < 1e-6 {
+        return None;
+    }
+    let c0 = [0, 1, 2].map(|i| (bb * ax[i] - ab * bx[i]) / det);
+    let c1 = [0, 1, 2].map(|i| (aa * bx[i] - ab * ax[i]) / det);
+    Some((c0, c1))
+}
+
+/// Encodes the 8 byte color part of a block in 4 color mode.
+fn encode_colors(pixels: &[[u8; 4]; 16], quality: CompressionQuality) -> [u8; 8] {
+    let (c0, c1) = principal_endpoints(pixels);
+    let mut endpoints = (to_565(c0), to_565(c1));
+    let (mut indices, mut error) = fit_indices(pixels, &palette(endpoints.0, endpoints.1));
+    for _ in 0..quality.refinements() {
+        let (c0, c1) = match least_squares_endpoints(pixels, &indices) {
+            Some(endpoints) => endpoints,
+            None => break,
+        };
+        let refined = (to_565(c0), to_565(c1));
+        let (refined_indices, refined_error) = fit_indices(pixels, &palette(refined.0, refined.1));
+        if refined_error >= error {
+            break;
+        }
+        (endpoints, indices, error) = (refined, refined_indices, refined_error);
+    }
+
+    // 4 color mode requires the first endpoint to be the larger one
+    if endpoints.0 < endpoints.1 {
+        endpoints = (endpoints.1, endpoints.0);
+        indices = indices.map(|i| i ^ 1);
+    } else if endpoints.0 == endpoints.1 {
+        indices = [0; 16];
+    }
+    let bits = indices.iter().rev().fold(0u32, |bits, &i| (bits << 2) | u32::from(i));
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&endpoints.0.to_le_bytes());
+    block[2..4].copy_from_slice(&endpoints.1.to_le_bytes());
+    block[4..].copy_from_slice(&bits.to_le_bytes());
+    block
+}
+
+/// The eight alpha values of a block in 8 alpha mode.
+fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 0];
+    for (i, alpha) in palette[2..].iter_mut().enumerate() {
+        let i = i as u16 + 1;
+        *alpha = (((7 - i) * u16::from(a0) + i * u16::from(a1)) / 7) as u8;
+    }
+    palette
+}
+
+/// Encodes the 8 byte alpha part of a DXT5 block.
+fn encode_alpha(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a0 = pixels.iter().map(|p| p[3]).max().unwrap();
+    let a1 = pixels.iter().map(|p| p[3]).min().unwrap();
+    let palette = alpha_palette(a0, a1);
+    let bits = pixels.iter().rev().fold(0u64, |bits, p| {
+        let index =
+            (0..8).min_by_key(|&i| (i16::from(palette[i]) - i16::from(p[3])).abs()).unwrap();
+        (bits << 3) | index as u64
+    });
+    let mut block = [0; 8];
+    block[0] = a0;
+    block[1] = a1;
+    block[2..].copy_from_slice(&bits.to_le_bytes()[..6]);
+    block
+}
+
+/// The DXT formats the encoder produces.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum EncodeFormat {
+    Dxt1,
+    Dxt5,
+}
+
+impl EncodeFormat {
+    /// DXT1 if every pixel of `rgba` is opaque, DXT5 otherwise.
+    pub(crate) fn for_pixels(rgba: &[u8]) -> Self {
+        if crate::pixels::is_opaque(rgba) {
+            EncodeFormat::Dxt1
+        } else {
+            EncodeFormat::Dxt5
+        }
+    }
+}
+
+impl From<EncodeFormat> for DxtFormat {
+    fn from(format: EncodeFormat) -> Self {
+        match format {
+            EncodeFormat::Dxt1 => DxtFormat::Dxt1,
+            EncodeFormat::Dxt5 => DxtFormat::Dxt5,
+        }
+    }
+}
+
+/// Compresses a `width` x `height` image of tightly packed RGBA pixels into
+/// DXT1 or DXT5 blocks, row by row. Partial blocks at the edges repeat the
+/// last row and column.
+pub(crate) fn compress(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    format: EncodeFormat,
+    quality: CompressionQuality,
+) -> Vec<u8> {
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let (row_len, rows) = DxtFormat::from(format).rows(width, height);
+    let mut out = Vec::with_capacity(row_len * rows);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let pixels: [[u8; 4]; 16] = std::array::from_fn(|i| {
+                let x = (bx * 4 + i % 4).min(width - 1);
+                let y = (by * 4 + i / 4).min(height - 1);
+                let offset = (y * width + x) * 4;
+                rgba[offset..offset + 4].try_into().unwrap()
+            });
+            match format {
+                EncodeFormat::Dxt1 => (),
+                EncodeFormat::Dxt5 => out.extend_from_slice(&encode_alpha(&pixels)),
+            }
+            out.extend_from_slice(&encode_colors(&pixels, quality));
+        }
+    }
+    out
+}
+
+/// Decompresses DXT blocks into tightly packed RGBA pixels.
+pub(crate) fn decompress(blocks: &[u8], width: usize, height: usize, format: DxtFormat) -> Vec<u8> {
+    let blocks_x = width.div_ceil(4);
+    let mut rgba = vec![0; width * height * 4];
+    for (b, block) in blocks.chunks_exact(format.block_size()).enumerate() {
+        let (bx, by) = (b % blocks_x, b / blocks_x);
+        let (alpha, colors) = match format {
+            DxtFormat::Dxt1 => (None, block),
+            DxtFormat::Dxt3 | DxtFormat::Dxt5 => (Some(&block[..8]), &block[8..]),
+        };
+        let c0 = u16::from_le_bytes([colors[0], colors[1]]);
+        let c1 = u16::from_le_bytes([colors[2], colors[3]]);
+        let mut palette = palette(c0, c1).map(|[r, g, b]| [r, g, b, 0xFF]);
+        if format == DxtFormat::Dxt1 && c0 <= c1 {
+            // 3 color mode with transparent black
+            let (a, b) = (from_565(c0), from_565(c1));
+            let mid = [0, 1, 2].map(|i| ((u16::from(a[i]) + u16::from(b[i])) / 2) as u8);
+            palette[2] = [mid[0], mid[1], mid[2], 0xFF];
+            palette[3] = [0; 4];
+        }
+        let bits = u32::from_le_bytes(colors[4..8].try_into().unwrap());
+        let alpha = alpha.map(|alpha| -> [u8; 16] {
+            if format == DxtFormat::Dxt3 {
+                let bits = u64::from_le_bytes(alpha.try_into().unwrap());
+                return std::array::from_fn(|i| ((bits >> (4 * i)) & 0xF) as u8 * 17);
+            }
+            let mut bits = [0; 8];
+            bits[..6].copy_from_slice(&alpha[2..]);
+            let bits = u64::from_le_bytes(bits);
+            let palette = if alpha[0] > alpha[1] {
+                alpha_palette(alpha[0], alpha[1])
+            } else {
+                let (a0, a1) = (u16::from(alpha[0]), u16::from(alpha[1]));
+                let mut palette = [alpha[0], alpha[1], 0, 0, 0, 0, 0, 0xFF];
+                for i in 1..5 {
+                    palette[i as usize + 1] = (((5 - i) * a0 + i * a1) / 5) as u8;
+                }
+                palette
+            };
+            std::array::from_fn(|i| palette[((bits >> (3 * i)) & 7) as usize])
+        });
+        for i in 0..16 {
+            let (x, y) = (bx * 4 + i % 4, by * 4 + i / 4);
+            if x >= width || y >= height {
+                continue;
+            }
+            let mut pixel = palette[((bits >> (2 * i)) & 3) as usize];
+            if let Some(alpha) = alpha {
+                pixel[3] = alpha[i];
+            }
+            let offset = (y * width + x) * 4;
+            rgba[offset..offset + 4].copy_from_slice(&pixel);
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUALITIES: [CompressionQuality; 3] =
+        [CompressionQuality::Fast, CompressionQuality::Normal, CompressionQuality::High];
+
+    /// Root mean square error over all channels.
+    fn rmse(a: &[u8], b: &[u8]) -> f64 {
+        let sum: f64 = a.iter().zip(b).map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2)).sum();
+        (sum / a.len() as f64).sqrt()
+    }
+
+    fn round_trip(rgba: &[u8], size: usize, quality: CompressionQuality) -> Vec<u8> {
+        let format = EncodeFormat::for_pixels(rgba);
+        let blocks = compress(rgba, size, size, format, quality);
+        let format = DxtFormat::from(format);
+        assert_eq!(blocks.len(), size.div_ceil(4).pow(2) * format.block_size());
+        decompress(&blocks, size, size, format)
+    }
+
+    fn image(size: usize, mut pixel: impl FnMut(usize, usize) -> [u8; 4]) -> Vec<u8> {
+        (0..size * size).flat_map(|i| pixel(i % size, i / size)).collect()
+    }
+
+    /// Deterministic noise from a linear congruential generator.
+    fn noise(size: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        image(size, |_, _| {
+            let mut next = || {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            };
+            [next(), next(), next(), 0xFF]
+        })
+    }
+
+    #[test]
+    fn picks_format_by_alpha() {
+        assert_eq!(EncodeFormat::for_pixels(&[1, 2, 3, 255, 4, 5, 6, 255]), EncodeFormat::Dxt1);
+        assert_eq!(EncodeFormat::for_pixels(&[1, 2, 3, 255, 4, 5, 6, 254]), EncodeFormat::Dxt5);
+    }
+
+    #[test]
+    fn representable_colors_are_exact() {
+        // both colors survive the 565 quantization unchanged
+        let rgba =
+            image(8, |x, y| if (x + y) % 3 == 0 { [255, 0, 66, 255] } else { [8, 130, 255, 255] });
+        for quality in QUALITIES {
+            assert_eq!(round_trip(&rgba, 8, quality), rgba);
+        }
+    }
+
+    #[test]
+    fn solid_blocks_use_4_color_mode() {
+        let rgba = image(4, |_, _| [100, 100, 100, 255]);
+        let blocks = compress(&rgba, 4, 4, EncodeFormat::Dxt1, CompressionQuality::High);
+        let c0 = u16::from_le_bytes([blocks[0], blocks[1]]);
+        let c1 = u16::from_le_bytes([blocks[2], blocks[3]]);
+        assert!(c0 >= c1);
+        let decoded = decompress(&blocks, 4, 4, DxtFormat::Dxt1);
+        assert!(rmse(&decoded, &rgba) < 3.0);
+        assert!(decoded.chunks(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn gradients_stay_within_error_bounds() {
+        let rgba = image(16, |x, _| [(x * 16) as u8, 255 - (x * 16) as u8, 128, 255]);
+        for quality in QUALITIES {
+            let error = rmse(&round_trip(&rgba, 16, quality), &rgba);
+            assert!(error < 3.0, "{:?}: {}", quality, error);
+        }
+    }
+
+    #[test]
+    fn refinement_never_increases_error() {
+        let rgba = noise(32, 7);
+        let errors = QUALITIES.map(|quality| rmse(&round_trip(&rgba, 32, quality), &rgba));
+        assert!(errors[1] <= errors[0] && errors[2] <= errors[1], "{:?}", errors);
+        // noise is the worst case, but still far from random output
+        assert!(errors[2] < 48.0, "{:?}", errors);
+    }
+
+    #[test]
+    fn interpolates_alpha() {
+        let rgba = image(4, |x, y| [255, 255, 255, ((y * 4 + x) * 17) as u8]);
+        let decoded = round_trip(&rgba, 4, CompressionQuality::Normal);
+        // the 8 alpha values are 255 / 7 apart
+        for (a, b) in decoded.chunks(4).zip(rgba.chunks(4)) {
+            assert!((i16::from(a[3]) - i16::from(b[3])).abs() <= 19, "{} {}", a[3], b[3]);
+        }
+        assert_eq!((decoded[3], decoded[63]), (0, 255));
+    }
+
+    #[test]
+    fn decodes_explicit_alpha() {
+        // alpha i / 15 for the i-th pixel, all colors white
+        let mut block = 0xFEDC_BA98_7654_3210u64.to_le_bytes().to_vec();
+        block.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+        let decoded = decompress(&block, 4, 4, DxtFormat::Dxt3);
+        assert_eq!(decoded, image(4, |x, y| [255, 255, 255, ((y * 4 + x) * 17) as u8]));
+    }
+
+    #[test]
+    fn handles_partial_blocks() {
+        let rgba = image(2, |x, _| if x == 0 { [0, 0, 0, 255] } else { [255, 255, 255, 255] });
+        let decoded = round_trip(&rgba, 2, CompressionQuality::Fast);
+        assert_eq!(decoded, rgba);
+    }
+}
@@ -0,0 +1,99 @@
+//! Owning handles of textures in a renderer's textures registry.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, Weak};
+
+use imgui::TextureId;
+
+/// Keeps a texture in the textures registry of the renderer that created it.
+///
+/// Dropping the handle does not release the texture right away, as the
+/// [`DrawData`](imgui::DrawData) of the current frame may still reference
+/// it. Instead it is removed from the registry by the next
+/// [`Renderer::end_frame`](crate::Renderer::end_frame), so the textures of
+/// dropped handles leak should that never be called.
+#[derive(Debug)]
+pub struct TextureHandle {
+    id: TextureId,
+    registry: Weak<Mutex<HandleRegistry>>,
+}
+
+impl TextureHandle {
+    /// The id under which the texture can be used in imgui, for example with
+    /// [`Ui::image`].
+    ///
+    /// [`Ui::image`]: https://docs.rs/imgui/*/imgui/struct.Ui.html#method.image
+    pub fn id(&self) -> TextureId {
+        self.id
+    }
+}
+
+impl From<&TextureHandle> for TextureId {
+    fn from(handle: &TextureHandle) -> Self {
+        handle.id
+    }
+}
+
+impl Drop for TextureHandle {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            let mut registry = registry.lock().unwrap();
+            registry.live.remove(&self.id);
+            registry.dropped.push(self.id);
+        }
+    }
+}
+
+/// Book keeping of the handles of one renderer.
+#[derive(Debug, Default)]
+pub(crate) struct HandleRegistry {
+    live: HashSet<TextureId>,
+    dropped: Vec<TextureId>,
+}
+
+impl HandleRegistry {
+    /// Creates the handle owning the texture `id`.
+    pub(crate) fn handle(registry: &Arc<Mutex<Self>>, id: TextureId) -> TextureHandle {
+        registry.lock().unwrap().live.insert(id);
+        TextureHandle { id, registry: Arc::downgrade(registry) }
+    }
+
+    /// Takes the ids of the handles dropped since the last call.
+    pub(crate) fn take_dropped(&mut self) -> Vec<TextureId> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// The ids of the handles that are still alive, sorted.
+    pub(crate) fn live(&self) -> Vec<TextureId> {
+        let mut live: Vec<_> = self.live.iter().copied().collect();
+        live.sort_by_key(|id| id.id());
+        live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropping_defers_release() {
+        let registry = Arc::new(Mutex::new(HandleRegistry::default()));
+        let a = HandleRegistry::handle(&registry, TextureId::new(1));
+        let b = HandleRegistry::handle(&registry, TextureId::new(2));
+        assert_eq!(TextureId::from(&b), TextureId::new(2));
+        assert_eq!(registry.lock().unwrap().live(), [TextureId::new(1), TextureId::new(2)]);
+        drop(b);
+        let mut registry = registry.lock().unwrap();
+        assert_eq!(registry.live(), [a.id()]);
+        assert_eq!(registry.take_dropped(), [TextureId::new(2)]);
+        assert!(registry.take_dropped().is_empty());
+    }
+
+    #[test]
+    fn handles_may_outlive_the_registry() {
+        let registry = Arc::new(Mutex::new(HandleRegistry::default()));
+        let handle = HandleRegistry::handle(&registry, TextureId::new(3));
+        drop(registry);
+        drop(handle);
+    }
+}
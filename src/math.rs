@@ -0,0 +1,167 @@
+//! The little bit of matrix math the renderer needs, in Direct3D's row vector
+//! convention.
+
+use windows::Foundation::Numerics::Matrix4x4;
+
+fn to_rows(m: &Matrix4x4) -> [[f32; 4]; 4] {
+    [
+        [m.M11, m.M12, m.M13, m.M14],
+        [m.M21, m.M22, m.M23, m.M24],
+        [m.M31, m.M32, m.M33, m.M34],
+        [m.M41, m.M42, m.M43, m.M44],
+    ]
+}
+
+fn from_rows(r: [[f32; 4]; 4]) -> Matrix4x4 {
+    Matrix4x4 {
+        M11: r[0][0],
+        M12: r[0][1],
+        M13: r[0][2],
+        M14: r[0][3],
+        M21: r[1][0],
+        M22: r[1][1],
+        M23: r[1][2],
+        M24: r[1][3],
+        M31: r[2][0],
+        M32: r[2][1],
+        M33: r[2][2],
+        M34: r[2][3],
+        M41: r[3][0],
+        M42: r[3][1],
+        M43: r[3][2],
+        M44: r[3][3],
+    }
+}
+
+/// The matrix in column major order, as expected by `dp4` based vertex
+/// shaders.
+pub(crate) fn transpose(m: &Matrix4x4) -> [f32; 16] {
+    [
+        m.M11, m.M21, m.M31, m.M41, m.M12, m.M22, m.M32, m.M42, m.M13, m.M23, m.M33, m.M43, m.M14,
+        m.M24, m.M34, m.M44,
+    ]
+}
+
+/// `a * b`, that is the transformation `a` followed by `b`.
+pub(crate) fn mul(a: &Matrix4x4, b: &Matrix4x4) -> Matrix4x4 {
+    let (a, b) = (to_rows(a), to_rows(b));
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    from_rows(r)
+}
+
+/// The inverse of `m`, `None` if it is singular.
+pub(crate) fn inverse(m: &Matrix4x4) -> Option<Matrix4x4> {
+    let m = to_rows(m);
+    // 2x2 sub-determinants of the upper and lower two rows
+    let s = [
+        m[0][0] * m[1][1] - m[1][0] * m[0][1],
+        m[0][0] * m[1][2] - m[1][0] * m[0][2],
+        m[0][0] * m[1][3] - m[1][0] * m[0][3],
+        m[0][1] * m[1][2] - m[1][1] * m[0][2],
+        m[0][1] * m[1][3] - m[1][1] * m[0][3],
+        m[0][2] * m[1][3] - m[1][2] * m[0][3],
+    ];
+    let c = [
+        m[2][0] * m[3][1] - m[3][0] * m[2][1],
+        m[2][0] * m[3][2] - m[3][0] * m[2][2],
+        m[2][0] * m[3][3] - m[3][0] * m[2][3],
+        m[2][1] * m[3][2] - m[3][1] * m[2][2],
+        m[2][1] * m[3][3] - m[3][1] * m[2][3],
+        m[2][2] * m[3][3] - m[3][2] * m[2][3],
+    ];
+    let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let inv = 1.0 / det;
+    Some(from_rows([
+        [
+            (m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3]) * inv,
+            (-m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3]) * inv,
+            (m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3]) * inv,
+            (-m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3]) * inv,
+        ],
+        [
+            (-m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1]) * inv,
+            (m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1]) * inv,
+            (-m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1]) * inv,
+            (m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1]) * inv,
+        ],
+        [
+            (m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0]) * inv,
+            (-m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0]) * inv,
+            (m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0]) * inv,
+            (-m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0]) * inv,
+        ],
+        [
+            (-m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0]) * inv,
+            (m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0]) * inv,
+            (-m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0]) * inv,
+            (m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0]) * inv,
+        ],
+    ]))
+}
+
+/// Transforms the plane `p` by the inverse `m_inv` of a point transformation,
+/// so that points transformed by the latter keep their side of the plane.
+pub(crate) fn transform_plane(m_inv: &Matrix4x4, p: [f32; 4]) -> [f32; 4] {
+    let m = to_rows(m_inv);
+    let mut r = [0.0; 4];
+    for (i, v) in r.iter_mut().enumerate() {
+        *v = (0..4).map(|j| m[i][j] * p[j]).sum();
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: &Matrix4x4, b: &Matrix4x4) {
+        let (a, b) = (to_rows(a), to_rows(b));
+        for (a, b) in a.iter().flatten().zip(b.iter().flatten()) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn inverse_of_affine_transform() {
+        let m = from_rows([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 3.0, 0.0],
+            [0.0, -1.0, 0.0, 0.0],
+            [5.0, 6.0, 7.0, 1.0],
+        ]);
+        let identity = from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inv = inverse(&m).unwrap();
+        assert_near(&mul(&m, &inv), &identity);
+        assert_near(&mul(&inv, &m), &identity);
+        assert!(inverse(&from_rows([[1.0; 4]; 4])).is_none());
+    }
+
+    #[test]
+    fn transformed_plane_keeps_sides() {
+        // scale by 2 and translate by (10, 20)
+        let m = from_rows([
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [10.0, 20.0, 0.0, 1.0],
+        ]);
+        // x >= 4 before the transformation is x >= 18 after it
+        let p = transform_plane(&inverse(&m).unwrap(), [1.0, 0.0, 0.0, -4.0]);
+        let dist = |x: f32| p[0] * x + p[3];
+        assert!(dist(18.0).abs() < 1e-5);
+        assert!(dist(19.0) > 0.0 && dist(17.0) < 0.0);
+    }
+}
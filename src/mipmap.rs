@@ -0,0 +1,113 @@
+//! CPU generation of mip chains for 32 bit textures.
+
+use std::ops::Range;
+
+/// One level of a mip chain, tightly packed with 4 bytes per pixel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MipLevel {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+/// The rows or columns of a level `src_len` long that the `i`-th of `len`
+/// downsampled ones covers, the last one taking in the odd one out.
+fn span(i: usize, len: usize, src_len: usize) -> Range<usize> {
+    let end = if i + 1 == len { src_len } else { 2 * i + 2 };
+    2 * i..end
+}
+
+/// Halves `level` in both dimensions, down to 1, averaging 2x2 pixel blocks.
+/// The last row and column of odd sized levels are averaged into the last
+/// row and column of the result. Colors are weighted by their alpha, so
+/// that transparent pixels do not bleed into their neighbours.
+pub(crate) fn downsample(level: &MipLevel) -> MipLevel {
+    let (src_w, src_h) = (level.width as usize, level.height as usize);
+    let (width, height) = ((src_w / 2).max(1), (src_h / 2).max(1));
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let rows = span(y, height, src_h);
+        for x in 0..width {
+            let cols = span(x, width, src_w);
+            let count = (rows.len() * cols.len()) as u32;
+            // alpha weighted and plain color sums, and the alpha sum
+            let (mut weighted, mut plain, mut alpha) = ([0u32; 3], [0u32; 3], 0);
+            for sy in rows.clone() {
+                for sx in cols.clone() {
+                    let pixel = &level.data[(sy * src_w + sx) * 4..][..4];
+                    let a = u32::from(pixel[3]);
+                    for c in 0..3 {
+                        weighted[c] += u32::from(pixel[c]) * a;
+                        plain[c] += u32::from(pixel[c]);
+                    }
+                    alpha += a;
+                }
+            }
+            let color = |c: usize| match alpha {
+                // fully transparent blocks keep their plain average
+                0 => (plain[c] + count / 2) / count,
+                _ => (weighted[c] + alpha / 2) / alpha,
+            };
+            data.extend(
+                [color(0), color(1), color(2), (alpha + count / 2) / count].map(|v| v as u8),
+            );
+        }
+    }
+    MipLevel { width: width as u32, height: height as u32, data }
+}
+
+/// Builds the full mip chain of a `width` x `height` image, starting with
+/// the image itself and ending with a 1x1 level.
+pub(crate) fn mip_chain(width: u32, height: u32, data: Vec<u8>) -> Vec<MipLevel> {
+    let mut levels = vec![MipLevel { width, height, data }];
+    while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+        levels.push(downsample(last));
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_blocks() {
+        let level = MipLevel {
+            width: 2,
+            height: 2,
+            data: vec![0, 10, 255, 255, 4, 20, 255, 255, 8, 30, 0, 255, 12, 41, 0, 255],
+        };
+        assert_eq!(
+            downsample(&level),
+            MipLevel { width: 1, height: 1, data: vec![6, 25, 128, 255] }
+        );
+    }
+
+    #[test]
+    fn weights_colors_by_alpha() {
+        // opaque red next to transparent green
+        let level = MipLevel { width: 2, height: 1, data: vec![255, 0, 0, 255, 0, 255, 0, 0] };
+        assert_eq!(downsample(&level).data, [255, 0, 0, 128]);
+        let level = MipLevel { width: 2, height: 1, data: vec![255, 0, 0, 0, 0, 255, 0, 0] };
+        assert_eq!(downsample(&level).data, [128, 128, 0, 0]);
+    }
+
+    #[test]
+    fn averages_odd_edges_into_the_last_pixel() {
+        // a 5x1 row of gray values
+        let data = [0u8, 100, 200, 50, 250].iter().flat_map(|&v| [v, v, v, 255]).collect();
+        let level = downsample(&MipLevel { width: 5, height: 1, data });
+        assert_eq!((level.width, level.height), (2, 1));
+        assert_eq!(level.data, [50, 50, 50, 255, 167, 167, 167, 255]);
+    }
+
+    #[test]
+    fn builds_full_chain() {
+        let levels = mip_chain(8, 3, vec![255; 8 * 3 * 4]);
+        let sizes: Vec<_> = levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, [(8, 3), (4, 1), (2, 1), (1, 1)]);
+        assert!(levels.iter().all(|l| l.data.len() == (l.width * l.height * 4) as usize));
+        assert!(levels.iter().all(|l| l.data.iter().all(|&b| b == 255)));
+        assert_eq!(mip_chain(1, 1, vec![1, 2, 3, 4]).len(), 1);
+    }
+}
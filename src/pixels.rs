@@ -0,0 +1,236 @@
+//! Conversions between imgui's RGBA pixels and Direct3D texture formats.
+
+use crate::dxt::{self, DxtFormat};
+
+/// Pixels read back from the device, see [`Renderer::capture_frame`] and
+/// [`Renderer::read_texture`].
+///
+/// [`Renderer::capture_frame`]: crate::Renderer::capture_frame
+/// [`Renderer::read_texture`]: crate::Renderer::read_texture
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    /// The width in pixels.
+    pub width: u32,
+    /// The height in pixels.
+    pub height: u32,
+    /// `width * height` tightly packed RGBA pixels, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+/// The surface formats that can be converted into RGBA, named after their
+/// `D3DFORMAT`s.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PixelFormat {
+    A8R8G8B8,
+    X8R8G8B8,
+    A8B8G8R8,
+    X8B8G8R8,
+    R5G6B5,
+    X1R5G5B5,
+    A1R5G5B5,
+    A4R4G4B4,
+    Dxt(DxtFormat),
+}
+
+impl PixelFormat {
+    /// The uncompressed formats RGBA images can be uploaded in, see
+    /// [`for_upload`](Self::for_upload).
+    pub(crate) const UPLOAD_FORMATS: [PixelFormat; 3] =
+        [PixelFormat::A8R8G8B8, PixelFormat::X8R8G8B8, PixelFormat::A4R4G4B4];
+
+    /// The format to upload an image in, `A8R8G8B8` if it is `supported`,
+    /// otherwise `X8R8G8B8` for `opaque` images and `A4R4G4B4` as the last
+    /// resort. Falls back to `A8R8G8B8` if none of them is supported.
+    pub(crate) fn for_upload(opaque: bool, supported: impl Fn(PixelFormat) -> bool) -> Self {
+        Self::UPLOAD_FORMATS
+            .into_iter()
+            .filter(|&format| opaque || format != PixelFormat::X8R8G8B8)
+            .find(|&format| supported(format))
+            .unwrap_or(PixelFormat::A8R8G8B8)
+    }
+
+    /// The length of a row of `width` pixels, or of a row of blocks for
+    /// compressed formats, and the number of such rows in `height` pixels.
+    pub(crate) fn rows(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            PixelFormat::Dxt(format) => format.rows(width, height),
+            PixelFormat::R5G6B5
+            | PixelFormat::X1R5G5B5
+            | PixelFormat::A1R5G5B5
+            | PixelFormat::A4R4G4B4 => (width * 2, height),
+            _ => (width * 4, height),
+        }
+    }
+
+    /// The number of bytes a `width` x `height` surface with rows `pitch`
+    /// bytes apart spans, leaving out the padding after the last row.
+    pub(crate) fn surface_len(self, width: u32, height: u32, pitch: usize) -> usize {
+        let (row_len, rows) = self.rows(width as usize, height as usize);
+        pitch * (rows - 1) + row_len
+    }
+}
+
+/// Swaps the red and blue channel of tightly packed 32 bit pixels, turning
+/// RGBA into the byte order of `D3DFMT_A8R8G8B8` and back.
+pub(crate) fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Whether every pixel of the tightly packed RGBA `pixels` is opaque.
+pub(crate) fn is_opaque(pixels: &[u8]) -> bool {
+    pixels.chunks_exact(4).all(|p| p[3] == 0xFF)
+}
+
+/// Converts tightly packed RGBA pixels into `format`, one of the
+/// [`PixelFormat::UPLOAD_FORMATS`].
+pub(crate) fn from_rgba(format: PixelFormat, rgba: &[u8]) -> Vec<u8> {
+    match format {
+        PixelFormat::A8R8G8B8 | PixelFormat::X8R8G8B8 => {
+            let mut pixels = rgba.to_vec();
+            swap_red_blue(&mut pixels);
+            pixels
+        },
+        PixelFormat::A4R4G4B4 => rgba
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let [r, g, b, a] =
+                    [p[0], p[1], p[2], p[3]].map(|c| (u16::from(c) * 15 + 127) / 255);
+                ((a << 12) | (r << 8) | (g << 4) | b).to_le_bytes()
+            })
+            .collect(),
+        _ => unreachable!("{format:?} is not an upload format"),
+    }
+}
+
+/// Scales a channel of `bits` bits up to 8 bits.
+fn expand(value: u16, bits: u32) -> u8 {
+    (u32::from(value) * 255 / ((1 << bits) - 1)) as u8
+}
+
+/// Converts a locked `width` x `height` surface of `format`, whose rows lie
+/// `pitch` bytes apart, into an [`RgbaImage`]. Formats without alpha become
+/// opaque.
+pub(crate) fn to_rgba(
+    format: PixelFormat,
+    width: u32,
+    height: u32,
+    pitch: usize,
+    data: &[u8],
+) -> RgbaImage {
+    let (w, h) = (width as usize, height as usize);
+    let (row_len, rows) = format.rows(w, h);
+    let packed: Vec<u8> =
+        (0..rows).flat_map(|y| &data[y * pitch..y * pitch + row_len]).copied().collect();
+    let pixels = match format {
+        PixelFormat::Dxt(format) => dxt::decompress(&packed, w, h, format),
+        PixelFormat::A8R8G8B8
+        | PixelFormat::X8R8G8B8
+        | PixelFormat::A8B8G8R8
+        | PixelFormat::X8B8G8R8 => {
+            let mut pixels = packed;
+            if matches!(format, PixelFormat::A8R8G8B8 | PixelFormat::X8R8G8B8) {
+                swap_red_blue(&mut pixels);
+            }
+            if matches!(format, PixelFormat::X8R8G8B8 | PixelFormat::X8B8G8R8) {
+                pixels.chunks_exact_mut(4).for_each(|p| p[3] = 0xFF);
+            }
+            pixels
+        },
+        _ => packed
+            .chunks_exact(2)
+            .flat_map(|p| {
+                let p = u16::from_le_bytes([p[0], p[1]]);
+                match format {
+                    PixelFormat::R5G6B5 => {
+                        [expand(p >> 11, 5), expand((p >> 5) & 0x3F, 6), expand(p & 0x1F, 5), 0xFF]
+                    },
+                    PixelFormat::A4R4G4B4 => [
+                        expand((p >> 8) & 0xF, 4),
+                        expand((p >> 4) & 0xF, 4),
+                        expand(p & 0xF, 4),
+                        expand(p >> 12, 4),
+                    ],
+                    _ => {
+                        let alpha = format == PixelFormat::X1R5G5B5 || p >> 15 != 0;
+                        [
+                            expand((p >> 10) & 0x1F, 5),
+                            expand((p >> 5) & 0x1F, 5),
+                            expand(p & 0x1F, 5),
+                            if alpha { 0xFF } else { 0 },
+                        ]
+                    },
+                }
+            })
+            .collect(),
+    };
+    RgbaImage { width, height, pixels }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_red_and_blue() {
+        let mut pixels = [1, 2, 3, 4, 5, 6, 7, 8];
+        swap_red_blue(&mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn converts_32_bit_formats_skipping_row_padding() {
+        // 1x2 pixels with 4 bytes of padding after every row
+        let data = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        let rgba = |format| to_rgba(format, 1, 2, 8, &data).pixels;
+        assert_eq!(rgba(PixelFormat::A8R8G8B8), [3, 2, 1, 4, 7, 6, 5, 8]);
+        assert_eq!(rgba(PixelFormat::X8R8G8B8), [3, 2, 1, 255, 7, 6, 5, 255]);
+        assert_eq!(rgba(PixelFormat::A8B8G8R8), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(rgba(PixelFormat::X8B8G8R8), [1, 2, 3, 255, 5, 6, 7, 255]);
+    }
+
+    #[test]
+    fn expands_16_bit_formats() {
+        let rgba = |format, pixel: u16| to_rgba(format, 1, 1, 2, &pixel.to_le_bytes()).pixels;
+        assert_eq!(rgba(PixelFormat::R5G6B5, 0xF81F), [255, 0, 255, 255]);
+        assert_eq!(rgba(PixelFormat::R5G6B5, 0x07E0), [0, 255, 0, 255]);
+        assert_eq!(rgba(PixelFormat::X1R5G5B5, 0x7C00), [255, 0, 0, 255]);
+        assert_eq!(rgba(PixelFormat::A1R5G5B5, 0x7C00), [255, 0, 0, 0]);
+        assert_eq!(rgba(PixelFormat::A1R5G5B5, 0x801F), [0, 0, 255, 255]);
+        assert_eq!(rgba(PixelFormat::A4R4G4B4, 0x8F0F), [255, 0, 255, 136]);
+    }
+
+    #[test]
+    fn falls_back_to_supported_upload_formats() {
+        let only = |formats: &'static [PixelFormat]| move |f| formats.contains(&f);
+        let all = only(&PixelFormat::UPLOAD_FORMATS);
+        assert_eq!(PixelFormat::for_upload(false, all), PixelFormat::A8R8G8B8);
+        let no_argb = only(&[PixelFormat::X8R8G8B8, PixelFormat::A4R4G4B4]);
+        assert_eq!(PixelFormat::for_upload(true, no_argb), PixelFormat::X8R8G8B8);
+        assert_eq!(PixelFormat::for_upload(false, no_argb), PixelFormat::A4R4G4B4);
+        assert_eq!(PixelFormat::for_upload(false, only(&[])), PixelFormat::A8R8G8B8);
+    }
+
+    #[test]
+    fn converts_rgba_into_upload_formats() {
+        let rgba = [255, 0, 136, 17, 10, 20, 30, 255];
+        assert!(!is_opaque(&rgba));
+        assert_eq!(from_rgba(PixelFormat::A8R8G8B8, &rgba), [136, 0, 255, 17, 30, 20, 10, 255]);
+        let packed = from_rgba(PixelFormat::A4R4G4B4, &rgba);
+        assert_eq!(packed, [0x08, 0x1F, 0x12, 0xF1]);
+        let image = to_rgba(PixelFormat::A4R4G4B4, 2, 1, 4, &packed);
+        assert_eq!(image.pixels, [255, 0, 136, 17, 17, 17, 34, 255]);
+    }
+
+    #[test]
+    fn decompresses_dxt_surfaces() {
+        // a single DXT1 block of one color, padded to a pitch of 16 bytes
+        let mut data = vec![0; 16];
+        data[..2].copy_from_slice(&0xF800u16.to_le_bytes());
+        let image = to_rgba(PixelFormat::Dxt(DxtFormat::Dxt1), 2, 3, 16, &data);
+        assert_eq!((image.width, image.height), (2, 3));
+        assert!(image.pixels.chunks(4).all(|p| p == [255, 0, 0, 255]));
+        assert_eq!(image.pixels.len(), 2 * 3 * 4);
+    }
+}
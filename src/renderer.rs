@@ -0,0 +1,2008 @@
+use std::sync::{Arc, Mutex};
+use std::{collections::HashMap, ffi::c_void, fs, iter, mem, path::Path, ptr, slice};
+
+use imgui::{
+    internal::{RawCast, RawWrapper},
+    sys, BackendFlags, Context, DrawCmd, DrawCmdParams, DrawData, DrawIdx, DrawVert, FontAtlas,
+    TextureId, Textures,
+};
+use windows::Foundation::Numerics::Matrix4x4;
+use windows::Win32::Graphics::Direct3D9::{
+    IDirect3DBaseTexture9, IDirect3DCubeTexture9, IDirect3DDevice9, IDirect3DDevice9Ex,
+    IDirect3DIndexBuffer9, IDirect3DStateBlock9, IDirect3DSurface9, IDirect3DTexture9,
+    IDirect3DVertexBuffer9, IDirect3DVertexDeclaration9, IDirect3DVertexShader9, D3DBLENDOP_ADD,
+    D3DBLEND_INVSRCALPHA, D3DBLEND_ONE, D3DBLEND_SRCALPHA, D3DCAPS2_CANAUTOGENMIPMAP, D3DCAPS9,
+    D3DCMP_LESSEQUAL, D3DCUBEMAP_FACES, D3DCULL_NONE, D3DDECLMETHOD_DEFAULT, D3DDECLTYPE_FLOAT2,
+    D3DDECLTYPE_UBYTE4N, D3DDECLTYPE_UNUSED, D3DDECLUSAGE_COLOR, D3DDECLUSAGE_POSITION,
+    D3DDECLUSAGE_TEXCOORD, D3DDEVICE_CREATION_PARAMETERS, D3DDISPLAYMODE, D3DDTCAPS_UBYTE4N,
+    D3DFILL_SOLID, D3DFMT_A1R5G5B5, D3DFMT_A4R4G4B4, D3DFMT_A8B8G8R8, D3DFMT_A8R8G8B8, D3DFMT_DXT1,
+    D3DFMT_DXT3, D3DFMT_DXT5, D3DFMT_INDEX16, D3DFMT_INDEX32, D3DFMT_R5G6B5, D3DFMT_X1R5G5B5,
+    D3DFMT_X8B8G8R8, D3DFMT_X8R8G8B8, D3DFORMAT, D3DLOCKED_RECT, D3DLOCK_DISCARD, D3DLOCK_READONLY,
+    D3DMULTISAMPLE_NONE, D3DPOOL, D3DPOOL_DEFAULT, D3DPOOL_MANAGED, D3DPOOL_SYSTEMMEM,
+    D3DPT_TRIANGLELIST, D3DRS_ALPHABLENDENABLE, D3DRS_ALPHATESTENABLE, D3DRS_BLENDOP,
+    D3DRS_CLIPPING, D3DRS_CLIPPLANEENABLE, D3DRS_CULLMODE, D3DRS_DESTBLEND, D3DRS_DESTBLENDALPHA,
+    D3DRS_FILLMODE, D3DRS_FOGENABLE, D3DRS_LIGHTING, D3DRS_RANGEFOGENABLE, D3DRS_SCISSORTESTENABLE,
+    D3DRS_SEPARATEALPHABLENDENABLE, D3DRS_SHADEMODE, D3DRS_SPECULARENABLE, D3DRS_SRCBLEND,
+    D3DRS_SRCBLENDALPHA, D3DRS_STENCILENABLE, D3DRS_ZENABLE, D3DRS_ZFUNC, D3DRS_ZWRITEENABLE,
+    D3DRTYPE_TEXTURE, D3DSAMP_MAGFILTER, D3DSAMP_MINFILTER, D3DSAMP_MIPFILTER, D3DSBT_ALL,
+    D3DSHADE_GOURAUD, D3DSURFACE_DESC, D3DTEXF_LINEAR, D3DTEXF_NONE, D3DTEXTUREFILTERTYPE,
+    D3DTOP_DISABLE, D3DTOP_MODULATE, D3DTSS_ALPHAARG1, D3DTSS_ALPHAARG2, D3DTSS_ALPHAOP,
+    D3DTSS_COLORARG1, D3DTSS_COLORARG2, D3DTSS_COLOROP, D3DTS_PROJECTION, D3DTS_VIEW, D3DTS_WORLD,
+    D3DUSAGE_AUTOGENMIPMAP, D3DUSAGE_DYNAMIC, D3DUSAGE_RENDERTARGET, D3DUSAGE_WRITEONLY,
+    D3DVERTEXELEMENT9, D3DVIEWPORT9,
+};
+
+use windows::core::{ComInterface, Error, Interface, HRESULT};
+use windows::Win32::Foundation::{E_FAIL, HANDLE, RECT, WIN32_ERROR};
+#[cfg(feature = "docking")]
+use windows::Win32::Graphics::Direct3D9::IDirect3DSwapChain9;
+use windows::Win32::Graphics::Dxgi::DXGI_ERROR_INVALID_CALL;
+use windows::Win32::System::SystemServices::{
+    D3DCLEAR_TARGET, D3DFVF_DIFFUSE, D3DFVF_TEX1, D3DFVF_XYZ, D3DPTEXTURECAPS_POW2, D3DTA_DIFFUSE,
+    D3DTA_TEXTURE,
+};
+
+use crate::dds::{self, DdsTexture};
+#[cfg(feature = "image")]
+use crate::decode;
+use crate::dxt::{self, CompressionQuality, DxtFormat, EncodeFormat};
+use crate::handle::{HandleRegistry, TextureHandle};
+use crate::math;
+use crate::mipmap::{self, MipLevel};
+use crate::pixels::{self, PixelFormat, RgbaImage};
+use crate::validation::{self, DrawDataError};
+use crate::vertex::{self, CustomVertex};
+
+const D3DFVF_CUSTOMVERTEX: u32 = D3DFVF_XYZ | D3DFVF_DIFFUSE | D3DFVF_TEX1;
+
+const FALSE: u32 = 0;
+const TRUE: u32 = 1;
+
+const VERTEX_BUF_ADD_CAPACITY: usize = 5000;
+const INDEX_BUF_ADD_CAPACITY: usize = 10000;
+
+///Reexport of windows::core::Result<T>
+pub type Result<T> = windows::core::Result<T>;
+
+const MAT_IDENTITY: Matrix4x4 = Matrix4x4 {
+    M11: 1.0,
+    M12: 0.0,
+    M13: 0.0,
+    M14: 0.0,
+    M21: 0.0,
+    M22: 1.0,
+    M23: 0.0,
+    M24: 0.0,
+    M31: 0.0,
+    M32: 0.0,
+    M33: 1.0,
+    M34: 0.0,
+    M41: 0.0,
+    M42: 0.0,
+    M43: 0.0,
+    M44: 1.0,
+};
+
+/// Vertex declaration describing imgui's [`DrawVert`] layout as is.
+const DRAW_VERT_DECL: [D3DVERTEXELEMENT9; 4] = [
+    D3DVERTEXELEMENT9 {
+        Stream: 0,
+        Offset: 0,
+        Type: D3DDECLTYPE_FLOAT2.0 as u8,
+        Method: D3DDECLMETHOD_DEFAULT.0 as u8,
+        Usage: D3DDECLUSAGE_POSITION.0 as u8,
+        UsageIndex: 0,
+    },
+    D3DVERTEXELEMENT9 {
+        Stream: 0,
+        Offset: 8,
+        Type: D3DDECLTYPE_FLOAT2.0 as u8,
+        Method: D3DDECLMETHOD_DEFAULT.0 as u8,
+        Usage: D3DDECLUSAGE_TEXCOORD.0 as u8,
+        UsageIndex: 0,
+    },
+    D3DVERTEXELEMENT9 {
+        Stream: 0,
+        Offset: 16,
+        Type: D3DDECLTYPE_UBYTE4N.0 as u8,
+        Method: D3DDECLMETHOD_DEFAULT.0 as u8,
+        Usage: D3DDECLUSAGE_COLOR.0 as u8,
+        UsageIndex: 0,
+    },
+    // D3DDECL_END()
+    D3DVERTEXELEMENT9 {
+        Stream: 0xFF,
+        Offset: 0,
+        Type: D3DDECLTYPE_UNUSED.0 as u8,
+        Method: 0,
+        Usage: 0,
+        UsageIndex: 0,
+    },
+];
+
+/// Bytecode of the following vertex shader, which feeds [`DrawVert`]s
+/// into the fixed function pixel pipeline. `c0`-`c3` hold the transposed
+/// projection matrix.
+///
+/// ```text
+/// vs_2_0
+/// dcl_position v0
+/// dcl_texcoord v1
+/// dcl_color v2
+/// dp4 oPos.x, v0, c0
+/// dp4 oPos.y, v0, c1
+/// dp4 oPos.z, v0, c2
+/// dp4 oPos.w, v0, c3
+/// mov oD0, v2
+/// mov oT0.xy, v1
+/// ```
+#[rustfmt::skip]
+const DRAW_VERT_VS: [u32; 33] = [
+    0xFFFE_0200,
+    0x0200_001F, 0x8000_0000, 0x900F_0000,
+    0x0200_001F, 0x8000_0005, 0x900F_0001,
+    0x0200_001F, 0x8000_000A, 0x900F_0002,
+    0x0300_0009, 0xC001_0000, 0x90E4_0000, 0xA0E4_0000,
+    0x0300_0009, 0xC002_0000, 0x90E4_0000, 0xA0E4_0001,
+    0x0300_0009, 0xC004_0000, 0x90E4_0000, 0xA0E4_0002,
+    0x0300_0009, 0xC008_0000, 0x90E4_0000, 0xA0E4_0003,
+    0x0200_0001, 0xD00F_0000, 0x90E4_0002,
+    0x0200_0001, 0xE003_0000, 0x90E4_0001,
+    0x0000_FFFF,
+];
+
+/// Device objects required to upload imgui's vertex buffers without
+/// converting them first.
+struct DirectUpload {
+    decl: IDirect3DVertexDeclaration9,
+    shader: IDirect3DVertexShader9,
+}
+
+impl DirectUpload {
+    /// Creates the vertex declaration and shader if the device supports
+    /// `vs_2_0` and `D3DDECLTYPE_UBYTE4N`, returns `None` otherwise.
+    unsafe fn new(device: &IDirect3DDevice9, caps: &D3DCAPS9) -> Result<Option<Self>> {
+        if caps.VertexShaderVersion & 0xFFFF < 0x0200
+            || caps.DeclTypes & D3DDTCAPS_UBYTE4N as u32 == 0
+        {
+            return Ok(None);
+        }
+        Ok(Some(DirectUpload {
+            decl: device.CreateVertexDeclaration(DRAW_VERT_DECL.as_ptr())?,
+            shader: device.CreateVertexShader(DRAW_VERT_VS.as_ptr())?,
+        }))
+    }
+}
+
+/// A handle to an offscreen render target owned by a [`Renderer`].
+///
+/// Created with [`Renderer::create_render_target`] and drawn into with
+/// [`Renderer::render_to_texture`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct RenderTarget(TextureId);
+
+impl RenderTarget {
+    /// The id under which the texture of this render target can be used in
+    /// imgui, for example with [`Ui::image`].
+    ///
+    /// [`Ui::image`]: https://docs.rs/imgui/*/imgui/struct.Ui.html#method.image
+    #[inline]
+    pub fn texture_id(&self) -> TextureId {
+        self.0
+    }
+}
+
+/// How the [`DrawData`] is mapped onto the render target.
+enum Placement {
+    /// Orthographically into a pixel rectangle, clipped with scissor rects.
+    Screen(ScreenRect),
+    /// Into the 3D scene, clipped with user clip planes.
+    World(WorldTransform),
+}
+
+/// The area of the render target the [`DrawData`] is rendered into.
+struct ScreenRect {
+    viewport: D3DVIEWPORT9,
+    /// Scale from display to viewport coordinates.
+    clip_scale: [f32; 2],
+}
+
+impl ScreenRect {
+    /// The whole framebuffer described by `draw_data`.
+    fn framebuffer(draw_data: &DrawData) -> Self {
+        let fb_width = draw_data.display_size[0] * draw_data.framebuffer_scale[0];
+        let fb_height = draw_data.display_size[1] * draw_data.framebuffer_scale[1];
+        ScreenRect {
+            viewport: D3DVIEWPORT9 {
+                X: 0,
+                Y: 0,
+                Width: fb_width as _,
+                Height: fb_height as _,
+                MinZ: 0.0,
+                MaxZ: 1.0,
+            },
+            clip_scale: draw_data.framebuffer_scale,
+        }
+    }
+
+    /// The given pixel rectangle, `None` if it is empty or has a negative
+    /// origin.
+    fn rect(draw_data: &DrawData, rect: &RECT) -> Option<Self> {
+        if rect.left < 0 || rect.top < 0 || rect.right <= rect.left || rect.bottom <= rect.top {
+            return None;
+        }
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        Some(ScreenRect {
+            viewport: D3DVIEWPORT9 {
+                X: rect.left as _,
+                Y: rect.top as _,
+                Width: width as _,
+                Height: height as _,
+                MinZ: 0.0,
+                MaxZ: 1.0,
+            },
+            clip_scale: [
+                width as f32 / draw_data.display_size[0],
+                height as f32 / draw_data.display_size[1],
+            ],
+        })
+    }
+
+    /// Maps an imgui clip rectangle to a scissor rectangle in render target
+    /// pixels.
+    fn scissor_rect(&self, clip_rect: [f32; 4], clip_off: [f32; 2]) -> RECT {
+        let (x, y) = (self.viewport.X as i32, self.viewport.Y as i32);
+        let clip_scale = self.clip_scale;
+        RECT {
+            left: ((clip_rect[0] - clip_off[0]) * clip_scale[0]) as i32 + x,
+            top: ((clip_rect[1] - clip_off[1]) * clip_scale[1]) as i32 + y,
+            right: ((clip_rect[2] - clip_off[0]) * clip_scale[0]) as i32 + x,
+            bottom: ((clip_rect[3] - clip_off[1]) * clip_scale[1]) as i32 + y,
+        }
+    }
+}
+
+/// Depth buffer usage of [`Renderer::render_in_world`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// The UI is drawn on top of the scene.
+    Disabled,
+    /// The UI is depth tested against the scene, but does not write depth.
+    Test,
+    /// The UI is depth tested against the scene and writes its depth, so that
+    /// geometry drawn afterwards is occluded by it.
+    TestAndWrite,
+}
+
+/// How [`Renderer`] handles draw commands referencing a [`TextureId`] it does
+/// not know, see [`Renderer::set_missing_texture_policy`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissingTexturePolicy {
+    /// Nothing is drawn and `DXGI_ERROR_INVALID_CALL` is returned.
+    #[default]
+    Error,
+    /// The draw commands using a missing texture are skipped.
+    Skip,
+    /// The draw commands using a missing texture are drawn with a magenta
+    /// and black checkerboard.
+    Placeholder,
+}
+
+/// Edge length of the placeholder texture and of its checkerboard cells.
+const PLACEHOLDER_SIZE: usize = 16;
+const PLACEHOLDER_CELL: usize = 4;
+
+struct WorldTransform {
+    world: Matrix4x4,
+    view_proj: Matrix4x4,
+    depth: DepthMode,
+    /// Transforms clip planes from display coordinates into the space the
+    /// device expects them in, `None` if clipping is not possible.
+    clip_plane_transform: Option<Matrix4x4>,
+}
+
+impl WorldTransform {
+    /// Clips everything outside of `clip_rect` with the first four user clip
+    /// planes.
+    unsafe fn set_clip_planes(&self, device: &IDirect3DDevice9, clip_rect: [f32; 4]) -> Result<()> {
+        let Some(plane_transform) = &self.clip_plane_transform else { return Ok(()) };
+        let planes = [
+            [1.0, 0.0, 0.0, -clip_rect[0]],
+            [0.0, 1.0, 0.0, -clip_rect[1]],
+            [-1.0, 0.0, 0.0, clip_rect[2]],
+            [0.0, -1.0, 0.0, clip_rect[3]],
+        ];
+        for (i, plane) in planes.into_iter().enumerate() {
+            let plane = math::transform_plane(plane_transform, plane);
+            device.SetClipPlane(i as u32, plane.as_ptr())?;
+        }
+        Ok(())
+    }
+}
+
+struct RenderTargetTexture {
+    texture: IDirect3DTexture9,
+    base: IDirect3DBaseTexture9,
+    size: [u32; 2],
+}
+
+/// A DirectX 9 renderer for (Imgui-rs)[https://docs.rs/imgui/*/imgui/].
+pub struct Renderer {
+    device: IDirect3DDevice9,
+    device_ex: Option<IDirect3DDevice9Ex>,
+    font_textures: HashMap<TextureId, Option<IDirect3DBaseTexture9>>,
+    direct_upload: Option<DirectUpload>,
+    clip_planes_supported: bool,
+    vertex_buffer: Option<(IDirect3DVertexBuffer9, usize)>,
+    index_buffer: Option<(IDirect3DIndexBuffer9, usize)>,
+    textures: Textures<IDirect3DBaseTexture9>,
+    render_targets: HashMap<TextureId, Option<RenderTargetTexture>>,
+    next_reserved_id: usize,
+    missing_texture_policy: MissingTexturePolicy,
+    missing_textures: Vec<TextureId>,
+    placeholder_texture: Option<IDirect3DBaseTexture9>,
+    validate: bool,
+    validation_errors: Vec<DrawDataError>,
+    texture_compression: Option<CompressionQuality>,
+    generate_mipmaps: bool,
+    autogen_mipmaps: bool,
+    /// Whether textures whose size is not a power of two can have mip levels.
+    npot_mipmaps: bool,
+    upload_formats: Vec<PixelFormat>,
+    premultiplied_alpha: bool,
+    premultiplied_textures: HashMap<TextureId, bool>,
+    handles: Arc<Mutex<HandleRegistry>>,
+    /// The swap chains of the secondary viewports by window handle.
+    #[cfg(feature = "docking")]
+    pub(crate) swap_chains: HashMap<isize, IDirect3DSwapChain9>,
+    /// The last error rendering or presenting a secondary viewport.
+    #[cfg(feature = "docking")]
+    pub(crate) viewport_error: Option<Error>,
+}
+
+impl Renderer {
+    /// Creates a new renderer for the given [`IDirect3DDevice9`].
+    ///
+    /// If the device supports `vs_2_0` and `D3DDECLTYPE_UBYTE4N` vertex data
+    /// the imgui vertex buffers are copied into the device buffers as is,
+    /// otherwise every vertex is converted for the fixed function pipeline.
+    ///
+    /// Should `device` be an [`IDirect3DDevice9Ex`] the renderer makes use of
+    /// that, see [`new_ex`](Self::new_ex). Further contexts can be rendered
+    /// with the same renderer, see [`add_context`](Self::add_context).
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`IDirect3DDevice9`] pointer.
+    ///
+    /// [`IDirect3DDevice9`]: https://docs.rs/winapi/0.3/x86_64-pc-windows-msvc/winapi/shared/d3d9/struct.IDirect3DDevice9.html
+    pub unsafe fn new(ctx: &mut Context, device: IDirect3DDevice9) -> Result<Self> {
+        let mut renderer = Self::with_font_atlases(device, iter::empty())?;
+        renderer.add_context(ctx)?;
+        Ok(renderer)
+    }
+
+    /// Creates a new renderer for the given [`IDirect3DDevice9`] without an
+    /// imgui [`Context`], building a font texture for each of the already
+    /// built `atlases`, see [`add_font_atlas`](Self::add_font_atlas).
+    ///
+    /// Font atlases of contexts created through `imgui-sys` can be passed in
+    /// with [`FontAtlas::from_raw_mut`](imgui::internal::RawCast::from_raw_mut).
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`IDirect3DDevice9`] pointer.
+    pub unsafe fn with_font_atlases<'a>(
+        device: IDirect3DDevice9,
+        atlases: impl IntoIterator<Item = &'a mut FontAtlas>,
+    ) -> Result<Self> {
+        let mut caps: D3DCAPS9 = mem::zeroed();
+        device.GetDeviceCaps(&mut caps)?;
+        let direct_upload = DirectUpload::new(&device, &caps)?;
+        let autogen_mipmaps = Self::supports_autogen_mipmaps(&device, &caps);
+        let upload_formats = PixelFormat::UPLOAD_FORMATS
+            .into_iter()
+            .filter(|&format| {
+                Self::check_texture_format(&device, 0, d3d_format(format)).is_none_or(|r| r.is_ok())
+            })
+            .collect();
+        let mut renderer = Renderer {
+            device_ex: device.cast().ok(),
+            vertex_buffer: Some(Self::create_vertex_buffer(&device, direct_upload.is_some(), 0)?),
+            index_buffer: Some(Self::create_index_buffer(&device, 0)?),
+            device,
+            font_textures: HashMap::new(),
+            direct_upload,
+            clip_planes_supported: caps.MaxUserClipPlanes >= 4,
+            textures: Textures::new(),
+            render_targets: HashMap::new(),
+            next_reserved_id: !0,
+            missing_texture_policy: MissingTexturePolicy::Error,
+            missing_textures: Vec::new(),
+            placeholder_texture: None,
+            validate: false,
+            validation_errors: Vec::new(),
+            texture_compression: None,
+            generate_mipmaps: false,
+            autogen_mipmaps,
+            // only set with conditional or without non power of two support
+            npot_mipmaps: caps.TextureCaps & D3DPTEXTURECAPS_POW2 as u32 == 0,
+            upload_formats,
+            premultiplied_alpha: false,
+            premultiplied_textures: HashMap::new(),
+            handles: Arc::default(),
+            #[cfg(feature = "docking")]
+            swap_chains: HashMap::new(),
+            #[cfg(feature = "docking")]
+            viewport_error: None,
+        };
+        for fonts in atlases {
+            renderer.add_font_atlas(fonts)?;
+        }
+        Ok(renderer)
+    }
+
+    /// Creates a new renderer for the given [`IDirect3DDevice9`].
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`IDirect3DDevice9`] pointer.
+    ///
+    /// [`IDirect3DDevice9`]: https://docs.rs/winapi/0.3/x86_64-pc-windows-msvc/winapi/shared/d3d9/struct.IDirect3DDevice9.html
+    #[deprecated(note = "identical to `new`, use `from_raw_device` for raw device pointers")]
+    pub unsafe fn new_raw(im_ctx: &mut imgui::Context, device: IDirect3DDevice9) -> Result<Self> {
+        Self::new(im_ctx, device)
+    }
+
+    /// Creates a new renderer for the device behind a raw `IDirect3DDevice9`
+    /// pointer, as handed out by game engines, C++ hosts or other bindings.
+    ///
+    /// The renderer adds its own reference to the device, the caller keeps
+    /// the reference it passed in. Returns `DXGI_ERROR_INVALID_CALL` if
+    /// `device` is null.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be null or a valid `IDirect3DDevice9` pointer.
+    pub unsafe fn from_raw_device(ctx: &mut Context, device: *mut c_void) -> Result<Self> {
+        let device = IDirect3DDevice9::from_raw_borrowed(&device).ok_or(DXGI_ERROR_INVALID_CALL)?;
+        Self::new(ctx, device.clone())
+    }
+
+    /// Creates a new renderer for a device of the [`winapi`] bindings, see
+    /// [`from_raw_device`](Self::from_raw_device).
+    ///
+    /// # Safety
+    ///
+    /// `device` must be null or a valid `IDirect3DDevice9` pointer.
+    ///
+    /// [`winapi`]: https://docs.rs/winapi/0.3
+    #[cfg(feature = "winapi")]
+    pub unsafe fn from_winapi_device(
+        ctx: &mut Context,
+        device: *mut winapi::shared::d3d9::IDirect3DDevice9,
+    ) -> Result<Self> {
+        Self::from_raw_device(ctx, device.cast())
+    }
+
+    /// Creates a new renderer for the given [`IDirect3DDevice9Ex`].
+    ///
+    /// The resources of an Ex device survive a device reset, so
+    /// [`invalidate_device_objects`](Self::invalidate_device_objects) keeps
+    /// them, and textures shared by other devices can be opened with
+    /// [`open_shared_texture`](Self::open_shared_texture).
+    ///
+    /// # Safety
+    ///
+    /// `device` must be a valid [`IDirect3DDevice9Ex`] pointer.
+    pub unsafe fn new_ex(ctx: &mut Context, device: IDirect3DDevice9Ex) -> Result<Self> {
+        Self::new(ctx, device.cast()?)
+    }
+
+    /// The [`IDirect3DDevice9Ex`] interface of the device, `None` if it is a
+    /// plain [`IDirect3DDevice9`].
+    pub fn device_ex(&self) -> Option<&IDirect3DDevice9Ex> {
+        self.device_ex.as_ref()
+    }
+
+    /// Opens a texture shared by another device, for example a D3D11 texture
+    /// created with `D3D11_RESOURCE_MISC_SHARED`, and registers it in the
+    /// textures registry for as long as the returned handle lives.
+    ///
+    /// `width`, `height` and `format` have to match the shared resource,
+    /// which has to be a single level render target. Returns
+    /// `DXGI_ERROR_INVALID_CALL` if the device is not an
+    /// [`IDirect3DDevice9Ex`].
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a shared handle of a texture.
+    pub unsafe fn open_shared_texture(
+        &mut self,
+        handle: HANDLE,
+        width: u32,
+        height: u32,
+        format: D3DFORMAT,
+    ) -> Result<TextureHandle> {
+        let device = self.device_ex.as_ref().ok_or(DXGI_ERROR_INVALID_CALL)?;
+        let mut shared_handle = handle;
+        let mut texture: Option<IDirect3DTexture9> = None;
+        device.CreateTexture(
+            width,
+            height,
+            1,
+            D3DUSAGE_RENDERTARGET as u32,
+            format,
+            D3DPOOL_DEFAULT,
+            &mut texture,
+            &mut shared_handle,
+        )?;
+        Ok(self.insert_texture(texture.unwrap().cast()?))
+    }
+
+    /// Registers `texture` in the textures registry for as long as the
+    /// returned handle lives, see [`TextureHandle`].
+    pub fn insert_texture(&mut self, texture: IDirect3DBaseTexture9) -> TextureHandle {
+        let id = self.textures.insert(texture);
+        HandleRegistry::handle(&self.handles, id)
+    }
+
+    /// Creates a texture from `width * height` tightly packed RGBA pixels and
+    /// registers it for as long as the returned handle lives.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if the size is empty or does not
+    /// match the length of `rgba`.
+    pub fn create_texture_rgba(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<TextureHandle> {
+        if width == 0 || height == 0 || rgba.len() != width as usize * height as usize * 4 {
+            return Err(DXGI_ERROR_INVALID_CALL.into());
+        }
+        let mips = self.generate_mipmaps;
+        self.insert_image(MipLevel { width, height, data: rgba.to_vec() }, mips)
+    }
+
+    /// Decodes a BMP, JPEG, PNG or TGA image held in memory into a texture
+    /// with a full mip chain and registers it for as long as the returned
+    /// handle lives.
+    ///
+    /// The texture is `D3DFMT_A8R8G8B8`, on devices not supporting that
+    /// `D3DFMT_X8R8G8B8` for opaque images and `D3DFMT_A4R4G4B4` otherwise.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if the image cannot be decoded.
+    #[cfg(feature = "image")]
+    pub fn load_texture_from_memory(&mut self, bytes: &[u8]) -> Result<TextureHandle> {
+        let image = decode::decode_texture(bytes)
+            .map_err(|e| Error::new(DXGI_ERROR_INVALID_CALL, e.to_string().into()))?;
+        self.insert_image(image, true)
+    }
+
+    /// Like [`load_texture_from_memory`](Self::load_texture_from_memory),
+    /// but reads the image from the file at `path`.
+    #[cfg(feature = "image")]
+    pub fn load_texture_from_path(&mut self, path: impl AsRef<Path>) -> Result<TextureHandle> {
+        self.load_texture_from_memory(&read_file(path.as_ref())?)
+    }
+
+    /// Creates a texture from a DDS file held in memory and registers it for
+    /// as long as the returned handle lives.
+    ///
+    /// DXT1, DXT3 and DXT5 compressed as well as 32 bit RGB(A) files are
+    /// supported, including all of their mip levels, where the size of DXT
+    /// files has to be a multiple of 4. Cube maps are created as cube
+    /// textures, which imgui itself cannot draw, but custom callbacks can.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if the file cannot be parsed.
+    pub fn load_dds_from_memory(&mut self, bytes: &[u8]) -> Result<TextureHandle> {
+        let dds = dds::parse(bytes)
+            .map_err(|e| Error::new(DXGI_ERROR_INVALID_CALL, e.to_string().into()))?;
+        let texture = unsafe { self.create_dds_texture(&dds)? };
+        Ok(self.insert_texture(texture))
+    }
+
+    /// Like [`load_dds_from_memory`](Self::load_dds_from_memory), but reads
+    /// the file at `path`.
+    pub fn load_dds_from_path(&mut self, path: impl AsRef<Path>) -> Result<TextureHandle> {
+        self.load_dds_from_memory(&read_file(path.as_ref())?)
+    }
+
+    /// Sets whether textures created from RGBA pixels, by
+    /// [`create_texture_rgba`](Self::create_texture_rgba) or the image
+    /// loaders, are compressed on the CPU before they are uploaded, `None` by
+    /// default.
+    ///
+    /// Opaque images become `D3DFMT_DXT1` textures, all others
+    /// `D3DFMT_DXT5`. Images whose size is not a multiple of 4, which DXT
+    /// textures require, and devices without DXT support get uncompressed
+    /// textures as before. Compression is lossy, so it suits photos and
+    /// artwork rather than pixel art or text.
+    pub fn set_texture_compression(&mut self, quality: Option<CompressionQuality>) {
+        self.texture_compression = quality;
+    }
+
+    /// How textures created from RGBA pixels are compressed, see
+    /// [`set_texture_compression`](Self::set_texture_compression).
+    pub fn texture_compression(&self) -> Option<CompressionQuality> {
+        self.texture_compression
+    }
+
+    /// Sets whether textures created by
+    /// [`create_texture_rgba`](Self::create_texture_rgba) get a full mip
+    /// chain, `false` by default. The image loaders always build one.
+    ///
+    /// Mip levels keep images drawn smaller than their size, like thumbnails,
+    /// from aliasing. They are generated by the device with
+    /// `D3DUSAGE_AUTOGENMIPMAP` if it supports that for uncompressed
+    /// textures, and by averaging 2x2 pixel blocks on the CPU otherwise.
+    /// Textures with mip levels, including registered ones, are drawn with
+    /// linear mip filtering. Devices supporting sizes other than powers of two
+    /// only conditionally get a single level for such textures.
+    pub fn set_generate_mipmaps(&mut self, enabled: bool) {
+        self.generate_mipmaps = enabled;
+    }
+
+    /// Whether [`create_texture_rgba`](Self::create_texture_rgba) builds a
+    /// full mip chain, see [`set_generate_mipmaps`](Self::set_generate_mipmaps).
+    pub fn generate_mipmaps(&self) -> bool {
+        self.generate_mipmaps
+    }
+
+    /// Sets whether textures without a flag of their own, see
+    /// [`set_texture_premultiplied`](Self::set_texture_premultiplied), hold
+    /// premultiplied alpha, `false` by default.
+    ///
+    /// Premultiplied textures are blended with `D3DBLEND_ONE` instead of
+    /// `D3DBLEND_SRCALPHA`, which keeps them from getting dark fringes. The
+    /// vertex colors they are drawn with, for example the tint of
+    /// [`Ui::image`], should then be premultiplied as well. Font atlases
+    /// always use straight alpha.
+    ///
+    /// [`Ui::image`]: https://docs.rs/imgui/*/imgui/struct.Ui.html#method.image
+    pub fn set_premultiplied_alpha(&mut self, enabled: bool) {
+        self.premultiplied_alpha = enabled;
+    }
+
+    /// Whether textures without a flag of their own hold premultiplied
+    /// alpha, see [`set_premultiplied_alpha`](Self::set_premultiplied_alpha).
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+
+    /// Flags the texture `texture_id` as holding premultiplied or straight
+    /// alpha regardless of [`set_premultiplied_alpha`](Self::set_premultiplied_alpha),
+    /// `None` removes the flag again.
+    ///
+    /// This suits the textures of [`RenderTarget`]s, which hold premultiplied
+    /// alpha once rendered into. Flags are dropped together with the textures
+    /// of [`TextureHandle`]s and render targets.
+    pub fn set_texture_premultiplied(
+        &mut self,
+        texture_id: TextureId,
+        premultiplied: Option<bool>,
+    ) {
+        match premultiplied {
+            Some(premultiplied) => self.premultiplied_textures.insert(texture_id, premultiplied),
+            None => self.premultiplied_textures.remove(&texture_id),
+        };
+    }
+
+    /// Whether the texture `texture_id` is blended as holding premultiplied
+    /// alpha.
+    pub fn is_texture_premultiplied(&self, texture_id: TextureId) -> bool {
+        match self.premultiplied_textures.get(&texture_id) {
+            Some(&premultiplied) => premultiplied,
+            None => self.premultiplied_alpha && !self.font_textures.contains_key(&texture_id),
+        }
+    }
+
+    fn insert_image(&mut self, image: MipLevel, mips: bool) -> Result<TextureHandle> {
+        let texture = unsafe { self.create_texture_from_image(image, mips)? };
+        Ok(self.insert_texture(texture.cast()?))
+    }
+
+    /// The ids of the textures whose [`TextureHandle`]s are still alive,
+    /// sorted, for example to find handles that would outlive the renderer.
+    pub fn outstanding_handles(&self) -> Vec<TextureId> {
+        self.handles.lock().unwrap().live()
+    }
+
+    /// Releases the textures whose [`TextureHandle`]s have been dropped.
+    ///
+    /// A frame may render draw data still referencing such textures several
+    /// times, for the main viewport, the platform windows, further contexts
+    /// or with [`render_to_texture`](Self::render_to_texture) and
+    /// [`capture_frame`](Self::capture_frame), so this has to be called once
+    /// per frame after all of them.
+    pub fn end_frame(&mut self) {
+        let dropped = self.handles.lock().unwrap().take_dropped();
+        for id in dropped {
+            self.textures.remove(id);
+            self.premultiplied_textures.remove(&id);
+        }
+    }
+
+    #[cfg(feature = "docking")]
+    pub(crate) fn device(&self) -> &IDirect3DDevice9 {
+        &self.device
+    }
+
+    /// Releases the resources this renderer keeps in `D3DPOOL_DEFAULT`, which
+    /// has to happen before the device can be reset with
+    /// `IDirect3DDevice9::Reset`, for example after it has been lost. The
+    /// resources of an [`IDirect3DDevice9Ex`] survive resets, so nothing is
+    /// released for those.
+    ///
+    /// This includes the textures of all [`RenderTarget`]s, which are
+    /// recreated by the next [`render_to_texture`](Self::render_to_texture),
+    /// and the swap chains of the secondary viewports, which are recreated
+    /// when the viewports are rendered next.
+    /// Rendering fails with `DXGI_ERROR_INVALID_CALL` until
+    /// [`create_device_objects`](Self::create_device_objects) has been called.
+    pub fn invalidate_device_objects(&mut self) {
+        if self.device_ex.is_some() {
+            return;
+        }
+        self.font_textures.values_mut().for_each(|tex| *tex = None);
+        self.vertex_buffer = None;
+        self.index_buffer = None;
+        self.render_targets.values_mut().for_each(|rt| *rt = None);
+        self.placeholder_texture = None;
+        #[cfg(feature = "docking")]
+        self.swap_chains.clear();
+    }
+
+    /// Takes the last error rendering or presenting a secondary viewport
+    /// with the [`ViewportRenderer`](crate::ViewportRenderer) since the last
+    /// call, as those cannot be returned through imgui.
+    #[cfg(feature = "docking")]
+    pub fn take_viewport_error(&mut self) -> Option<Error> {
+        self.viewport_error.take()
+    }
+
+    /// Recreates the resources released by
+    /// [`invalidate_device_objects`](Self::invalidate_device_objects) after
+    /// the device has been reset.
+    ///
+    /// The font texture is rebuilt from the font atlas of `ctx`.
+    pub fn create_device_objects(&mut self, ctx: &mut Context) -> Result<()> {
+        self.create_device_objects_with_fonts([ctx.fonts()])
+    }
+
+    /// Like [`create_device_objects`](Self::create_device_objects), but
+    /// rebuilds the font textures of the given `atlases`. Font textures of
+    /// atlases not passed in stay released, rendering with them fails.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if an atlas has not been added to
+    /// this renderer.
+    pub fn create_device_objects_with_fonts<'a>(
+        &mut self,
+        atlases: impl IntoIterator<Item = &'a mut FontAtlas>,
+    ) -> Result<()> {
+        unsafe {
+            for fonts in atlases {
+                let slot =
+                    self.font_textures.get_mut(&fonts.tex_id).ok_or(DXGI_ERROR_INVALID_CALL)?;
+                if slot.is_none() {
+                    *slot = Some(Self::create_font_texture(fonts, &self.device)?.cast()?);
+                }
+            }
+            if self.vertex_buffer.is_none() {
+                let direct_upload = self.direct_upload.is_some();
+                self.vertex_buffer =
+                    Some(Self::create_vertex_buffer(&self.device, direct_upload, 0)?);
+            }
+            if self.index_buffer.is_none() {
+                self.index_buffer = Some(Self::create_index_buffer(&self.device, 0)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the font texture of `fonts` and stores the id it is drawn with
+    /// in the atlas' `tex_id`.
+    ///
+    /// A renderer can draw the frames of several imgui contexts as long as
+    /// their font atlases have been added.
+    pub fn add_font_atlas(&mut self, fonts: &mut FontAtlas) -> Result<TextureId> {
+        let texture = unsafe { Self::create_font_texture(fonts, &self.device)?.cast()? };
+        let id = self.reserve_id();
+        self.font_textures.insert(id, Some(texture));
+        fonts.tex_id = id;
+        Ok(id)
+    }
+
+    /// Sets up `ctx` to be rendered by this renderer next to the contexts it
+    /// already renders, adding its font atlas with
+    /// [`add_font_atlas`](Self::add_font_atlas).
+    ///
+    /// All contexts share the vertex and index buffers as well as the
+    /// textures registry of this renderer, their [`DrawData`] is passed to
+    /// [`render`](Self::render) as usual. After a device reset
+    /// [`create_device_objects`](Self::create_device_objects) has to be
+    /// called for each of them.
+    pub fn add_context(&mut self, ctx: &mut Context) -> Result<TextureId> {
+        let id = self.add_font_atlas(ctx.fonts())?;
+        ctx.io_mut().backend_flags |= BackendFlags::RENDERER_HAS_VTX_OFFSET;
+        ctx.set_renderer_name(String::from(concat!(
+            "imgui_dx9_renderer@",
+            env!("CARGO_PKG_VERSION")
+        )));
+        Ok(id)
+    }
+
+    /// Stops rendering `ctx`, releasing its font texture.
+    pub fn remove_context(&mut self, ctx: &mut Context) {
+        self.remove_font_atlas(ctx.fonts().tex_id);
+        ctx.io_mut().backend_flags.remove(BackendFlags::RENDERER_HAS_VTX_OFFSET);
+        ctx.set_renderer_name(None);
+    }
+
+    /// Removes the font texture with the given id, returning it unless it
+    /// has been released by
+    /// [`invalidate_device_objects`](Self::invalidate_device_objects).
+    pub fn remove_font_atlas(&mut self, id: TextureId) -> Option<IDirect3DBaseTexture9> {
+        self.font_textures.remove(&id).flatten()
+    }
+
+    fn reserve_id(&mut self) -> TextureId {
+        let id = TextureId::from(self.next_reserved_id);
+        self.next_reserved_id -= 1;
+        id
+    }
+
+    /// The textures registry of this renderer.
+    ///
+    /// Textures inserted here are owned by the caller and have to be removed
+    /// manually, [`insert_texture`](Self::insert_texture) hands out owning
+    /// handles instead. The texture slots from !0 downwards are reserved for font atlases and
+    /// [`RenderTarget`]s, therefore the renderer will ignore any texture
+    /// inserted into said slots.
+    #[inline]
+    pub fn textures_mut(&mut self) -> &mut Textures<IDirect3DBaseTexture9> {
+        &mut self.textures
+    }
+
+    /// The textures registry of this renderer.
+    #[inline]
+    pub fn textures(&self) -> &Textures<IDirect3DBaseTexture9> {
+        &self.textures
+    }
+
+    /// Creates a new offscreen render target.
+    ///
+    /// The backing texture is created lazily by
+    /// [`render_to_texture`](Self::render_to_texture).
+    pub fn create_render_target(&mut self) -> RenderTarget {
+        let id = self.reserve_id();
+        self.render_targets.insert(id, None);
+        RenderTarget(id)
+    }
+
+    /// The texture of the given render target, `None` if nothing has been
+    /// rendered into it yet.
+    pub fn render_target_texture(&self, target: &RenderTarget) -> Option<&IDirect3DTexture9> {
+        self.render_targets.get(&target.0)?.as_ref().map(|rt| &rt.texture)
+    }
+
+    /// Removes the given render target, returning its texture if it had one.
+    pub fn remove_render_target(&mut self, target: RenderTarget) -> Option<IDirect3DTexture9> {
+        self.premultiplied_textures.remove(&target.0);
+        self.render_targets.remove(&target.0)?.map(|rt| rt.texture)
+    }
+
+    /// Renders the given [`DrawData`] into the texture of `target` instead of
+    /// the currently bound render target.
+    ///
+    /// The texture is (re)created to match the framebuffer size of the
+    /// [`DrawData`] and cleared to transparent black before rendering. The
+    /// previously bound render target, depth stencil surface and viewport are
+    /// restored afterwards. Like [`render`](Self::render) this has to be
+    /// called between `BeginScene` and `EndScene`.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if the framebuffer size is empty or
+    /// `target` does not belong to this renderer.
+    pub fn render_to_texture(
+        &mut self,
+        draw_data: &DrawData,
+        target: &RenderTarget,
+    ) -> Result<IDirect3DTexture9> {
+        let size = framebuffer_size(draw_data)?;
+        unsafe {
+            let texture = self.sized_render_target(target, size)?;
+            self.render_into_surface(draw_data, &texture.GetSurfaceLevel(0)?)?;
+            Ok(texture)
+        }
+    }
+
+    /// Renders the given [`DrawData`] into an offscreen surface the size of
+    /// its framebuffer and reads the result back, for example for bug reports
+    /// or visual tests.
+    ///
+    /// The surface is cleared to transparent black before rendering, the
+    /// currently bound render target is left untouched. Unlike
+    /// [`render`](Self::render) this begins and ends a scene of its own, so
+    /// it has to be called outside of `BeginScene` and `EndScene`.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if the framebuffer size is empty.
+    pub fn capture_frame(&mut self, draw_data: &DrawData) -> Result<RgbaImage> {
+        let size = framebuffer_size(draw_data)?;
+        unsafe {
+            let mut surface: Option<IDirect3DSurface9> = None;
+            self.device.CreateRenderTarget(
+                size[0],
+                size[1],
+                D3DFMT_A8R8G8B8,
+                D3DMULTISAMPLE_NONE,
+                0,
+                false,
+                &mut surface,
+                ptr::null_mut(),
+            )?;
+            let surface = surface.unwrap();
+            self.device.BeginScene()?;
+            let rendered = self.render_into_surface(draw_data, &surface);
+            self.device.EndScene()?;
+            rendered?;
+            self.read_render_target(&surface)
+        }
+    }
+
+    /// Reads the top level of a texture of this renderer back, be it a
+    /// registered texture, a font atlas or the texture of a [`RenderTarget`].
+    ///
+    /// Render targets are copied into system memory first, all other textures
+    /// have to be lockable. Returns `DXGI_ERROR_INVALID_CALL` for unknown
+    /// ids, cube textures, default pool textures that are neither dynamic nor
+    /// render targets, and formats other than the 16 and 32 bit RGB(A) and
+    /// DXT ones.
+    pub fn read_texture(&self, texture_id: TextureId) -> Result<RgbaImage> {
+        let texture: IDirect3DTexture9 = self
+            .lookup_texture(texture_id)
+            .and_then(|texture| texture.cast().ok())
+            .ok_or(DXGI_ERROR_INVALID_CALL)?;
+        unsafe {
+            let surface = texture.GetSurfaceLevel(0)?;
+            let mut desc: D3DSURFACE_DESC = mem::zeroed();
+            surface.GetDesc(&mut desc)?;
+            if desc.Usage & D3DUSAGE_RENDERTARGET as u32 != 0 {
+                self.read_render_target(&surface)
+            } else if desc.Pool == D3DPOOL_DEFAULT && desc.Usage & D3DUSAGE_DYNAMIC as u32 == 0 {
+                Err(Error::new(DXGI_ERROR_INVALID_CALL, "texture is not lockable".into()))
+            } else {
+                Self::read_surface(&surface, &desc)
+            }
+        }
+    }
+
+    /// Renders into `surface` with the current render target, depth stencil
+    /// surface and viewport restored afterwards.
+    unsafe fn render_into_surface(
+        &mut self,
+        draw_data: &DrawData,
+        surface: &IDirect3DSurface9,
+    ) -> Result<()> {
+        let _target_guard = RenderTargetBackup::backup(&self.device)?;
+        self.device.SetRenderTarget(0, surface)?;
+        self.device.SetDepthStencilSurface(None)?;
+        self.device.Clear(0, ptr::null(), D3DCLEAR_TARGET as u32, 0, 1.0, 0)?;
+        self.render(draw_data)
+    }
+
+    /// Copies the render target `surface` into system memory and reads it.
+    unsafe fn read_render_target(&self, surface: &IDirect3DSurface9) -> Result<RgbaImage> {
+        let mut desc: D3DSURFACE_DESC = mem::zeroed();
+        surface.GetDesc(&mut desc)?;
+        let mut copy: Option<IDirect3DSurface9> = None;
+        self.device.CreateOffscreenPlainSurface(
+            desc.Width,
+            desc.Height,
+            desc.Format,
+            D3DPOOL_SYSTEMMEM,
+            &mut copy,
+            ptr::null_mut(),
+        )?;
+        let copy = copy.unwrap();
+        self.device.GetRenderTargetData(surface, &copy)?;
+        Self::read_surface(&copy, &desc)
+    }
+
+    /// Locks the lockable `surface` described by `desc` and converts its
+    /// pixels into RGBA.
+    unsafe fn read_surface(
+        surface: &IDirect3DSurface9,
+        desc: &D3DSURFACE_DESC,
+    ) -> Result<RgbaImage> {
+        let format = pixel_format(desc.Format).ok_or_else(|| {
+            Error::new(DXGI_ERROR_INVALID_CALL, "unsupported texture format".into())
+        })?;
+        let mut locked_rect: D3DLOCKED_RECT = D3DLOCKED_RECT { Pitch: 0, pBits: ptr::null_mut() };
+        surface.LockRect(&mut locked_rect, ptr::null(), D3DLOCK_READONLY as u32)?;
+        let pitch = locked_rect.Pitch as usize;
+        let len = format.surface_len(desc.Width, desc.Height, pitch);
+        let data = slice::from_raw_parts(locked_rect.pBits as *const u8, len);
+        let image = pixels::to_rgba(format, desc.Width, desc.Height, pitch, data);
+        surface.UnlockRect()?;
+        Ok(image)
+    }
+
+    unsafe fn sized_render_target(
+        &mut self,
+        target: &RenderTarget,
+        size: [u32; 2],
+    ) -> Result<IDirect3DTexture9> {
+        let slot = self.render_targets.get_mut(&target.0).ok_or(DXGI_ERROR_INVALID_CALL)?;
+        match slot {
+            Some(rt) if rt.size == size => Ok(rt.texture.clone()),
+            _ => {
+                // release the old texture before creating its replacement
+                *slot = None;
+                let mut texture: Option<IDirect3DTexture9> = None;
+                self.device.CreateTexture(
+                    size[0],
+                    size[1],
+                    1,
+                    D3DUSAGE_RENDERTARGET as u32,
+                    D3DFMT_A8R8G8B8,
+                    D3DPOOL_DEFAULT,
+                    &mut texture,
+                    ptr::null_mut(),
+                )?;
+                let texture = texture.unwrap();
+                *slot = Some(RenderTargetTexture {
+                    base: texture.cast()?,
+                    texture: texture.clone(),
+                    size,
+                });
+                Ok(texture)
+            },
+        }
+    }
+
+    /// Sets how draw commands referencing a texture that is neither in the
+    /// textures registry nor a font atlas or [`RenderTarget`] of this
+    /// renderer are handled.
+    ///
+    /// Regardless of the policy such textures are reported by
+    /// [`missing_textures`](Self::missing_textures).
+    pub fn set_missing_texture_policy(&mut self, policy: MissingTexturePolicy) {
+        self.missing_texture_policy = policy;
+    }
+
+    /// How missing textures are handled, [`MissingTexturePolicy::Error`] by
+    /// default.
+    pub fn missing_texture_policy(&self) -> MissingTexturePolicy {
+        self.missing_texture_policy
+    }
+
+    /// The ids of the missing textures the [`DrawData`] of the last render
+    /// call referenced, each listed once.
+    pub fn missing_textures(&self) -> &[TextureId] {
+        &self.missing_textures
+    }
+
+    /// Enables or disables validating the [`DrawData`] before every render
+    /// call, see [`validate`](Self::validate).
+    ///
+    /// Invalid draw data is not drawn at all, `DXGI_ERROR_INVALID_CALL` is
+    /// returned and the problems are reported by
+    /// [`validation_errors`](Self::validation_errors). Validation walks every
+    /// index, so it is meant for debugging.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validate = enabled;
+        self.validation_errors.clear();
+    }
+
+    /// The problems validation found in the [`DrawData`] of the last render
+    /// call, see [`set_validation`](Self::set_validation).
+    pub fn validation_errors(&self) -> &[DrawDataError] {
+        &self.validation_errors
+    }
+
+    /// Checks `draw_data` for commands that would make the device read out of
+    /// bounds or draw garbage.
+    ///
+    /// Every command needs to describe whole triangles within the index
+    /// buffer of its draw list, whose indices plus `vtx_offset` have to lie
+    /// within the vertex buffer. Clip rectangles have to be finite and
+    /// textures known to this renderer, regardless of the
+    /// [`missing_texture_policy`](Self::missing_texture_policy).
+    pub fn validate(&self, draw_data: &DrawData) -> Vec<DrawDataError> {
+        let draw_lists = draw_data.draw_lists().map(|draw_list| {
+            (draw_list.vtx_buffer().len(), draw_list.idx_buffer(), draw_list.commands())
+        });
+        validation::validate_draw_lists(draw_lists, |id| self.lookup_texture(id).is_some())
+    }
+
+    /// Renders the given [`Ui`] with this renderer.
+    ///
+    /// Should the [`DrawData`] reference a texture this renderer does not
+    /// know, it is handled according to the
+    /// [`missing_texture_policy`](Self::missing_texture_policy). With
+    /// [`MissingTexturePolicy::Error`] `DXGI_ERROR_INVALID_CALL` is returned
+    /// before anything is drawn.
+    ///
+    /// [`Ui`]: https://docs.rs/imgui/*/imgui/struct.Ui.html
+    pub fn render(&mut self, draw_data: &DrawData) -> Result<()> {
+        self.render_placed(draw_data, &Placement::Screen(ScreenRect::framebuffer(draw_data)))
+    }
+
+    /// Renders the `ImDrawData` of an imgui context that is not managed by
+    /// imgui-rs, for example one created through `imgui-sys` by a plugin.
+    ///
+    /// The font atlas of the context has to be added to this renderer, see
+    /// [`add_font_atlas`](Self::add_font_atlas). Returns
+    /// `DXGI_ERROR_INVALID_CALL` if `draw_data` is null.
+    ///
+    /// # Safety
+    ///
+    /// `draw_data` must be null or point to valid draw data of the imgui
+    /// version imgui-rs is built against.
+    pub unsafe fn render_raw(&mut self, draw_data: *const sys::ImDrawData) -> Result<()> {
+        let draw_data = draw_data.as_ref().ok_or(DXGI_ERROR_INVALID_CALL)?;
+        self.render(DrawData::from_raw(draw_data))
+    }
+
+    /// Renders the given [`DrawData`] into the `target_rect` pixel rectangle of
+    /// the currently bound render target, instead of its top left corner.
+    ///
+    /// The display area of the [`DrawData`] is stretched to the size of
+    /// `target_rect`, so for a pixel perfect result its size should match
+    /// `display_size * framebuffer_scale`.
+    ///
+    /// Returns `DXGI_ERROR_INVALID_CALL` if `target_rect` is empty or starts
+    /// at a negative coordinate.
+    pub fn render_with_viewport(&mut self, draw_data: &DrawData, target_rect: RECT) -> Result<()> {
+        let rect = ScreenRect::rect(draw_data, &target_rect).ok_or(DXGI_ERROR_INVALID_CALL)?;
+        self.render_placed(draw_data, &Placement::Screen(rect))
+    }
+
+    /// Renders the given [`DrawData`] into the 3D scene, for example onto an
+    /// in game terminal.
+    ///
+    /// `world` transforms display coordinates, as used by imgui, into world
+    /// space, while `view_proj` is the view projection matrix of the scene.
+    /// Both use the row vector convention of Direct3D. As the UI lies in the
+    /// `z = 0` plane the third row of `world` does not move it, but it should
+    /// keep `world` invertible.
+    ///
+    /// Clip rectangles are applied with the first four user clip planes,
+    /// should the device support less than that or `world * view_proj` not be
+    /// invertible the UI is drawn unclipped. The viewport is left as is.
+    pub fn render_in_world(
+        &mut self,
+        draw_data: &DrawData,
+        world: Matrix4x4,
+        view_proj: Matrix4x4,
+        depth: DepthMode,
+    ) -> Result<()> {
+        let clip_plane_transform = if !self.clip_planes_supported {
+            None
+        } else if self.direct_upload.is_some() {
+            // clip planes are specified in clip space when using vertex shaders
+            math::inverse(&math::mul(&world, &view_proj))
+        } else {
+            math::inverse(&world)
+        };
+        let transform = WorldTransform { world, view_proj, depth, clip_plane_transform };
+        self.render_placed(draw_data, &Placement::World(transform))
+    }
+
+    fn render_placed(&mut self, draw_data: &DrawData, placement: &Placement) -> Result<()> {
+        if draw_data.display_size[0] < 0.0 || draw_data.display_size[1] < 0.0 {
+            return Ok(());
+        }
+        let (vertex_buffer, index_buffer) = match (&self.vertex_buffer, &self.index_buffer) {
+            (Some(vertex_buffer), Some(index_buffer)) => (vertex_buffer.1, index_buffer.1),
+            // the device objects have been invalidated
+            _ => return Err(DXGI_ERROR_INVALID_CALL.into()),
+        };
+        if self.validate {
+            self.validation_errors = self.validate(draw_data);
+            if !self.validation_errors.is_empty() {
+                return Err(DXGI_ERROR_INVALID_CALL.into());
+            }
+        }
+        self.find_missing_textures(draw_data);
+        unsafe {
+            if !self.missing_textures.is_empty() {
+                match self.missing_texture_policy {
+                    MissingTexturePolicy::Error => return Err(DXGI_ERROR_INVALID_CALL.into()),
+                    MissingTexturePolicy::Skip => (),
+                    MissingTexturePolicy::Placeholder if self.placeholder_texture.is_none() => {
+                        let pixels = checkerboard();
+                        let size = PLACEHOLDER_SIZE as u32;
+                        let texture = Self::create_texture(&self.device, size, size, &pixels)?;
+                        self.placeholder_texture = Some(texture.cast()?);
+                    },
+                    MissingTexturePolicy::Placeholder => (),
+                }
+            }
+            if vertex_buffer < draw_data.total_vtx_count as usize {
+                self.vertex_buffer = Some(Self::create_vertex_buffer(
+                    &self.device,
+                    self.direct_upload.is_some(),
+                    draw_data.total_vtx_count as usize,
+                )?);
+            }
+            if index_buffer < draw_data.total_idx_count as usize {
+                self.index_buffer = Some(Self::create_index_buffer(
+                    &self.device,
+                    draw_data.total_idx_count as usize,
+                )?);
+            }
+
+            let _state_guard = StateBackup::backup(&self.device)?;
+
+            self.set_render_state(draw_data, placement)?;
+            self.write_buffers(draw_data)?;
+            self.render_impl(draw_data, placement)
+        }
+    }
+
+    unsafe fn render_impl(&mut self, draw_data: &DrawData, placement: &Placement) -> Result<()> {
+        let clip_off = draw_data.display_pos;
+        let mut last_clip_rect = None;
+        let mut vertex_offset = 0;
+        let mut index_offset = 0;
+        let mut last_tex = None;
+        let mut texture_bound = false;
+        // the blending set_render_state sets up and the one the bound
+        // texture needs
+        let mut blend_premultiplied = false;
+        let mut texture_premultiplied = false;
+        for draw_list in draw_data.draw_lists() {
+            for cmd in draw_list.commands() {
+                match cmd {
+                    DrawCmd::Elements {
+                        count,
+                        cmd_params: DrawCmdParams { clip_rect, texture_id, .. },
+                    } => {
+                        if last_tex != Some(texture_id) {
+                            texture_bound = match self.resolve_texture(texture_id) {
+                                Some(texture) => {
+                                    self.device.SetTexture(0, texture)?;
+                                    let filter = Self::mip_filter(texture);
+                                    self.device.SetSamplerState(
+                                        0,
+                                        D3DSAMP_MIPFILTER,
+                                        filter.0 as u32,
+                                    )?;
+                                    true
+                                },
+                                None => false,
+                            };
+                            texture_premultiplied = self.is_texture_premultiplied(texture_id);
+                            last_tex = Some(texture_id);
+                        }
+                        if !texture_bound {
+                            // skipped as demanded by the missing texture policy
+                            index_offset += count;
+                            continue;
+                        }
+                        if blend_premultiplied != texture_premultiplied {
+                            let src_blend = if texture_premultiplied {
+                                D3DBLEND_ONE
+                            } else {
+                                D3DBLEND_SRCALPHA
+                            };
+                            self.device.SetRenderState(D3DRS_SRCBLEND, src_blend.0)?;
+                            blend_premultiplied = texture_premultiplied;
+                        }
+
+                        match placement {
+                            Placement::Screen(screen) => {
+                                let r = screen.scissor_rect(clip_rect, clip_off);
+                                self.device.SetScissorRect(&r)?;
+                            },
+                            Placement::World(world) if last_clip_rect != Some(clip_rect) => {
+                                world.set_clip_planes(&self.device, clip_rect)?;
+                                last_clip_rect = Some(clip_rect);
+                            },
+                            Placement::World(_) => (),
+                        }
+                        self.device.DrawIndexedPrimitive(
+                            D3DPT_TRIANGLELIST,
+                            vertex_offset as i32,
+                            0,
+                            draw_list.vtx_buffer().len() as u32,
+                            index_offset as u32,
+                            count as u32 / 3,
+                        )?;
+                        index_offset += count;
+                    },
+                    DrawCmd::ResetRenderState => {
+                        self.set_render_state(draw_data, placement)?;
+                        last_clip_rect = None;
+                        blend_premultiplied = false;
+                    },
+                    DrawCmd::RawCallback { callback, raw_cmd } => {
+                        callback(draw_list.raw(), raw_cmd)
+                    },
+                }
+            }
+            vertex_offset += draw_list.vtx_buffer().len();
+        }
+        Ok(())
+    }
+
+    /// Collects the textures `draw_data` references that cannot be found
+    /// into `missing_textures`.
+    fn find_missing_textures(&mut self, draw_data: &DrawData) {
+        self.missing_textures.clear();
+        let mut last_tex = None;
+        for draw_list in draw_data.draw_lists() {
+            for cmd in draw_list.commands() {
+                if let DrawCmd::Elements { cmd_params: DrawCmdParams { texture_id, .. }, .. } = cmd
+                {
+                    if last_tex != Some(texture_id)
+                        && self.lookup_texture(texture_id).is_none()
+                        && !self.missing_textures.contains(&texture_id)
+                    {
+                        self.missing_textures.push(texture_id);
+                    }
+                    last_tex = Some(texture_id);
+                }
+            }
+        }
+    }
+
+    /// Looks up a texture, falling back to the placeholder texture if the
+    /// missing texture policy asks for it.
+    fn resolve_texture(&self, texture_id: TextureId) -> Option<&IDirect3DBaseTexture9> {
+        self.lookup_texture(texture_id).or(match self.missing_texture_policy {
+            MissingTexturePolicy::Placeholder => self.placeholder_texture.as_ref(),
+            _ => None,
+        })
+    }
+
+    fn lookup_texture(&self, texture_id: TextureId) -> Option<&IDirect3DBaseTexture9> {
+        if let Some(font_tex) = self.font_textures.get(&texture_id) {
+            font_tex.as_ref()
+        } else if let Some(rt) = self.render_targets.get(&texture_id) {
+            rt.as_ref().map(|rt| &rt.base)
+        } else {
+            self.textures.get(texture_id)
+        }
+    }
+
+    unsafe fn set_render_state(
+        &mut self,
+        draw_data: &DrawData,
+        placement: &Placement,
+    ) -> Result<()> {
+        let device = &self.device;
+        device.SetPixelShader(None)?;
+        device.SetRenderState(D3DRS_FILLMODE, D3DFILL_SOLID.0 as u32)?;
+        device.SetRenderState(D3DRS_SHADEMODE, D3DSHADE_GOURAUD.0 as u32)?;
+        device.SetRenderState(D3DRS_ALPHATESTENABLE, FALSE)?;
+        device.SetRenderState(D3DRS_CULLMODE, D3DCULL_NONE.0)?;
+        device.SetRenderState(D3DRS_ALPHABLENDENABLE, TRUE)?;
+        device.SetRenderState(D3DRS_BLENDOP, D3DBLENDOP_ADD.0)?;
+        device.SetRenderState(D3DRS_SRCBLEND, D3DBLEND_SRCALPHA.0)?;
+        device.SetRenderState(D3DRS_DESTBLEND, D3DBLEND_INVSRCALPHA.0)?;
+        device.SetRenderState(D3DRS_SEPARATEALPHABLENDENABLE, TRUE)?;
+        device.SetRenderState(D3DRS_SRCBLENDALPHA, D3DBLEND_ONE.0)?;
+        device.SetRenderState(D3DRS_DESTBLENDALPHA, D3DBLEND_INVSRCALPHA.0)?;
+        device.SetRenderState(D3DRS_FOGENABLE, FALSE)?;
+        device.SetRenderState(D3DRS_RANGEFOGENABLE, FALSE)?;
+        device.SetRenderState(D3DRS_SPECULARENABLE, FALSE)?;
+        device.SetRenderState(D3DRS_STENCILENABLE, FALSE)?;
+        device.SetRenderState(D3DRS_CLIPPING, TRUE)?;
+        device.SetRenderState(D3DRS_LIGHTING, FALSE)?;
+        device.SetTextureStageState(0, D3DTSS_COLOROP, D3DTOP_MODULATE.0 as u32)?;
+        device.SetTextureStageState(0, D3DTSS_COLORARG1, D3DTA_TEXTURE)?;
+        device.SetTextureStageState(0, D3DTSS_COLORARG2, D3DTA_DIFFUSE)?;
+        device.SetTextureStageState(0, D3DTSS_ALPHAOP, D3DTOP_MODULATE.0 as u32)?;
+        device.SetTextureStageState(0, D3DTSS_ALPHAARG1, D3DTA_TEXTURE)?;
+        device.SetTextureStageState(0, D3DTSS_ALPHAARG2, D3DTA_DIFFUSE)?;
+        device.SetTextureStageState(1, D3DTSS_COLOROP, D3DTOP_DISABLE.0 as u32)?;
+        device.SetTextureStageState(1, D3DTSS_ALPHAOP, D3DTOP_DISABLE.0 as u32)?;
+        device.SetSamplerState(0, D3DSAMP_MINFILTER, D3DTEXF_LINEAR.0 as u32)?;
+        device.SetSamplerState(0, D3DSAMP_MAGFILTER, D3DTEXF_LINEAR.0 as u32)?;
+
+        let mat_transform = match placement {
+            Placement::Screen(screen) => {
+                device.SetViewport(&screen.viewport)?;
+                device.SetRenderState(D3DRS_ZENABLE, FALSE)?;
+                device.SetRenderState(D3DRS_ZWRITEENABLE, FALSE)?;
+                device.SetRenderState(D3DRS_SCISSORTESTENABLE, TRUE)?;
+                device.SetRenderState(D3DRS_CLIPPLANEENABLE, 0)?;
+
+                let l = draw_data.display_pos[0] + 0.5;
+                let r = draw_data.display_pos[0] + draw_data.display_size[0] + 0.5;
+                let t = draw_data.display_pos[1] + 0.5;
+                let b = draw_data.display_pos[1] + draw_data.display_size[1] + 0.5;
+                let mat_projection = Matrix4x4 {
+                    M11: 2.0 / (r - l),
+                    M12: 0.0,
+                    M13: 0.0,
+                    M14: 0.0,
+                    M21: 0.0,
+                    M22: 2.0 / (t - b),
+                    M23: 0.0,
+                    M24: 0.0,
+                    M31: 0.0,
+                    M32: 0.0,
+                    M33: 0.5,
+                    M34: 0.0,
+                    M41: (l + r) / (l - r),
+                    M42: (t + b) / (b - t),
+                    M43: 0.5,
+                    M44: 1.0,
+                };
+
+                device.SetTransform(D3DTS_WORLD, &MAT_IDENTITY)?;
+                device.SetTransform(D3DTS_VIEW, &MAT_IDENTITY)?;
+                device.SetTransform(D3DTS_PROJECTION, &mat_projection)?;
+                mat_projection
+            },
+            Placement::World(world) => {
+                let (z_enable, z_write) = match world.depth {
+                    DepthMode::Disabled => (FALSE, FALSE),
+                    DepthMode::Test => (TRUE, FALSE),
+                    DepthMode::TestAndWrite => (TRUE, TRUE),
+                };
+                device.SetRenderState(D3DRS_ZENABLE, z_enable)?;
+                device.SetRenderState(D3DRS_ZWRITEENABLE, z_write)?;
+                device.SetRenderState(D3DRS_ZFUNC, D3DCMP_LESSEQUAL.0 as u32)?;
+                device.SetRenderState(D3DRS_SCISSORTESTENABLE, FALSE)?;
+                let clip_planes = if world.clip_plane_transform.is_some() { 0b1111 } else { 0 };
+                device.SetRenderState(D3DRS_CLIPPLANEENABLE, clip_planes)?;
+
+                device.SetTransform(D3DTS_WORLD, &world.world)?;
+                device.SetTransform(D3DTS_VIEW, &MAT_IDENTITY)?;
+                device.SetTransform(D3DTS_PROJECTION, &world.view_proj)?;
+                math::mul(&world.world, &world.view_proj)
+            },
+        };
+        match &self.direct_upload {
+            Some(direct) => {
+                let mat_transposed = math::transpose(&mat_transform);
+                device.SetVertexShader(&direct.shader)?;
+                device.SetVertexShaderConstantF(0, mat_transposed.as_ptr(), 4)?;
+            },
+            None => device.SetVertexShader(None)?,
+        }
+        Ok(())
+    }
+
+    unsafe fn lock_buffers<'v, 'i, V>(
+        vb: &'v mut IDirect3DVertexBuffer9,
+        ib: &'i mut IDirect3DIndexBuffer9,
+        vtx_count: usize,
+        idx_count: usize,
+    ) -> Result<(&'v mut [V], &'i mut [DrawIdx])> {
+        let mut vtx_dst: *mut V = ptr::null_mut();
+        let mut idx_dst: *mut DrawIdx = ptr::null_mut();
+
+        vb.Lock(
+            0,
+            (vtx_count * mem::size_of::<V>()) as u32,
+            &mut vtx_dst as *mut _ as _,
+            D3DLOCK_DISCARD as u32,
+        )?;
+
+        match ib.Lock(
+            0,
+            (idx_count * mem::size_of::<DrawIdx>()) as u32,
+            &mut idx_dst as *mut _ as _,
+            D3DLOCK_DISCARD as u32,
+        ) {
+            Ok(_) => Ok((
+                slice::from_raw_parts_mut(vtx_dst, vtx_count),
+                slice::from_raw_parts_mut(idx_dst, idx_count),
+            )),
+            Err(e) => {
+                vb.Unlock()?;
+                Err(e)
+            },
+        }
+    }
+
+    unsafe fn write_buffers(&mut self, draw_data: &DrawData) -> Result<()> {
+        if self.direct_upload.is_some() {
+            self.write_buffers_with(draw_data, |vbuf, vtx_dst: &mut [DrawVert]| {
+                vtx_dst.copy_from_slice(vbuf)
+            })?;
+        } else {
+            self.write_buffers_with(draw_data, vertex::convert_vertices)?;
+        }
+        match &self.direct_upload {
+            Some(direct) => self.device.SetVertexDeclaration(&direct.decl),
+            None => self.device.SetFVF(D3DFVF_CUSTOMVERTEX),
+        }
+    }
+
+    /// Copies the index buffers of `draw_data` into the device index buffer
+    /// and writes its vertex buffers into the device vertex buffer with
+    /// `write_vertices`, binding both and using a stride of `V` for the
+    /// latter.
+    unsafe fn write_buffers_with<V>(
+        &mut self,
+        draw_data: &DrawData,
+        mut write_vertices: impl FnMut(&[DrawVert], &mut [V]),
+    ) -> Result<()> {
+        let (vb, ib) = match (&mut self.vertex_buffer, &mut self.index_buffer) {
+            (Some((vb, _)), Some((ib, _))) => (vb, ib),
+            _ => return Err(DXGI_ERROR_INVALID_CALL.into()),
+        };
+        let (mut vtx_dst, mut idx_dst) = Self::lock_buffers::<V>(
+            vb,
+            ib,
+            draw_data.total_vtx_count as usize,
+            draw_data.total_idx_count as usize,
+        )?;
+
+        for (vbuf, ibuf) in
+            draw_data.draw_lists().map(|draw_list| (draw_list.vtx_buffer(), draw_list.idx_buffer()))
+        {
+            write_vertices(vbuf, &mut vtx_dst[..vbuf.len()]);
+            idx_dst[..ibuf.len()].copy_from_slice(ibuf);
+            vtx_dst = &mut vtx_dst[vbuf.len()..];
+            idx_dst = &mut idx_dst[ibuf.len()..];
+        }
+        vb.Unlock()?;
+        ib.Unlock()?;
+        self.device.SetIndices(&*ib)?;
+        self.device.SetStreamSource(0, &*vb, 0, mem::size_of::<V>() as u32)
+    }
+
+    unsafe fn create_vertex_buffer(
+        device: &IDirect3DDevice9,
+        direct_upload: bool,
+        vtx_count: usize,
+    ) -> Result<(IDirect3DVertexBuffer9, usize)> {
+        let len = vtx_count + VERTEX_BUF_ADD_CAPACITY;
+        let (stride, fvf) = if direct_upload {
+            (mem::size_of::<DrawVert>(), 0)
+        } else {
+            (mem::size_of::<CustomVertex>(), D3DFVF_CUSTOMVERTEX)
+        };
+        let mut vertex_buffer: Option<IDirect3DVertexBuffer9> = None;
+        device.CreateVertexBuffer(
+            (len * stride) as u32,
+            (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as u32,
+            fvf,
+            D3DPOOL_DEFAULT,
+            &mut vertex_buffer,
+            ptr::null_mut(),
+        )?;
+        Ok((vertex_buffer.unwrap(), len))
+    }
+
+    unsafe fn create_index_buffer(
+        device: &IDirect3DDevice9,
+        idx_count: usize,
+    ) -> Result<(IDirect3DIndexBuffer9, usize)> {
+        let len = idx_count + INDEX_BUF_ADD_CAPACITY;
+        let mut index_buffer: Option<IDirect3DIndexBuffer9> = None;
+
+        device.CreateIndexBuffer(
+            (len * mem::size_of::<DrawIdx>()) as u32,
+            (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as u32,
+            if mem::size_of::<DrawIdx>() == 2 { D3DFMT_INDEX16 } else { D3DFMT_INDEX32 },
+            D3DPOOL_DEFAULT,
+            &mut index_buffer,
+            ptr::null_mut(),
+        )?;
+        Ok((index_buffer.unwrap(), len))
+    }
+
+    // FIXME, imgui hands us an rgba texture while we make dx9 think it receives an
+    // argb texture
+    unsafe fn create_font_texture(
+        fonts: &mut FontAtlas,
+        device: &IDirect3DDevice9,
+    ) -> Result<IDirect3DTexture9> {
+        let texture = fonts.build_rgba32_texture();
+        Self::create_texture(device, texture.width, texture.height, texture.data)
+    }
+
+    /// Creates a texture from tightly packed 32 bit pixels.
+    unsafe fn create_texture(
+        device: &IDirect3DDevice9,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<IDirect3DTexture9> {
+        let mut texture_handle: Option<IDirect3DTexture9> = None;
+
+        device.CreateTexture(
+            width,
+            height,
+            1,
+            D3DUSAGE_DYNAMIC as u32,
+            D3DFMT_A8R8G8B8,
+            D3DPOOL_DEFAULT,
+            &mut texture_handle,
+            ptr::null_mut(),
+        )?;
+
+        let result_texture = texture_handle.unwrap();
+        Self::write_level(&result_texture, 0, width, height, data)?;
+        Ok(result_texture)
+    }
+
+    /// Creates a texture from an RGBA image, in a pool that survives device
+    /// resets.
+    ///
+    /// The texture is DXT compressed if enabled and possible, otherwise it is
+    /// `D3DFMT_A8R8G8B8` or, should the device not support that, the best
+    /// supported fallback, see [`PixelFormat::for_upload`]. With `mips` its
+    /// mip chain is generated by the device where supported and on the CPU
+    /// otherwise, unless the device can only mipmap power of two sizes and
+    /// the image is not one.
+    unsafe fn create_texture_from_image(
+        &self,
+        image: MipLevel,
+        mips: bool,
+    ) -> Result<IDirect3DTexture9> {
+        let (width, height) = (image.width, image.height);
+        let mips =
+            mips && (self.npot_mipmaps || width.is_power_of_two() && height.is_power_of_two());
+        let compression = self.texture_compression.filter(|_| width % 4 == 0 && height % 4 == 0);
+        let format = PixelFormat::for_upload(pixels::is_opaque(&image.data), |format| {
+            self.upload_formats.contains(&format)
+        });
+        // devices cannot generate the mip levels of compressed textures
+        let autogen = mips
+            && self.autogen_mipmaps
+            && compression.is_none()
+            && format == PixelFormat::A8R8G8B8;
+        let mut levels = if mips && !autogen {
+            mipmap::mip_chain(width, height, image.data)
+        } else {
+            vec![image]
+        };
+
+        if let Some(quality) = compression {
+            let format = EncodeFormat::for_pixels(&levels[0].data);
+            let d3d_format = d3d_format(PixelFormat::Dxt(format.into()));
+            // devices without DXT support get an uncompressed texture
+            if let Ok(texture) =
+                self.create_upload_texture(width, height, levels.len() as u32, 0, d3d_format)
+            {
+                for (i, level) in levels.iter().enumerate() {
+                    let (w, h) = (level.width as usize, level.height as usize);
+                    let blocks = dxt::compress(&level.data, w, h, format, quality);
+                    let (row_len, _) = DxtFormat::from(format).rows(w, h);
+                    Self::write_rows(&texture, i as u32, &blocks, row_len)?;
+                }
+                return Ok(texture);
+            }
+        }
+
+        if autogen {
+            let usage = D3DUSAGE_AUTOGENMIPMAP as u32;
+            match self.create_upload_texture(width, height, 0, usage, D3DFMT_A8R8G8B8) {
+                Ok(texture) => {
+                    Self::write_rgba_level(&texture, 0, &levels[0], format)?;
+                    texture.SetAutoGenFilterType(D3DTEXF_LINEAR)?;
+                    texture.GenerateMipSubLevels();
+                    return Ok(texture);
+                },
+                Err(_) => {
+                    let image = levels.pop().unwrap();
+                    levels = mipmap::mip_chain(width, height, image.data);
+                },
+            }
+        }
+
+        let texture =
+            self.create_upload_texture(width, height, levels.len() as u32, 0, d3d_format(format))?;
+        for (i, level) in levels.iter().enumerate() {
+            Self::write_rgba_level(&texture, i as u32, level, format)?;
+        }
+        Ok(texture)
+    }
+
+    /// Whether the device can generate the mip levels of `D3DFMT_A8R8G8B8`
+    /// textures.
+    unsafe fn supports_autogen_mipmaps(device: &IDirect3DDevice9, caps: &D3DCAPS9) -> bool {
+        // a success code of CheckDeviceFormat the windows crate maps to Ok
+        const D3DOK_NOAUTOGEN: HRESULT = HRESULT(0x0876_086F);
+
+        if caps.Caps2 & D3DCAPS2_CANAUTOGENMIPMAP as u32 == 0 {
+            return false;
+        }
+        let usage = D3DUSAGE_AUTOGENMIPMAP as u32;
+        Self::check_texture_format(device, usage, D3DFMT_A8R8G8B8)
+            .is_some_and(|result| result.is_ok() && result != D3DOK_NOAUTOGEN)
+    }
+
+    /// Asks whether the device can create textures of `format` with `usage`,
+    /// returning the raw result of `CheckDeviceFormat`, or `None` if the
+    /// device could not be queried.
+    unsafe fn check_texture_format(
+        device: &IDirect3DDevice9,
+        usage: u32,
+        format: D3DFORMAT,
+    ) -> Option<HRESULT> {
+        let mut params: D3DDEVICE_CREATION_PARAMETERS = mem::zeroed();
+        let mut mode: D3DDISPLAYMODE = mem::zeroed();
+        device.GetCreationParameters(&mut params).ok()?;
+        device.GetDisplayMode(0, &mut mode).ok()?;
+        let d3d = device.GetDirect3D().ok()?;
+        Some((Interface::vtable(&d3d).CheckDeviceFormat)(
+            Interface::as_raw(&d3d),
+            params.AdapterOrdinal,
+            params.DeviceType,
+            mode.Format,
+            usage,
+            D3DRTYPE_TEXTURE,
+            format,
+        ))
+    }
+
+    /// The mip filter to draw `texture` with, linear if it has mip levels.
+    unsafe fn mip_filter(texture: &IDirect3DBaseTexture9) -> D3DTEXTUREFILTERTYPE {
+        // textures with generated mip levels report a single level
+        let autogen = texture.cast::<IDirect3DTexture9>().is_ok_and(|texture| {
+            let mut desc: D3DSURFACE_DESC = mem::zeroed();
+            texture.GetLevelDesc(0, &mut desc).is_ok()
+                && desc.Usage & D3DUSAGE_AUTOGENMIPMAP as u32 != 0
+        });
+        if autogen || texture.GetLevelCount() > 1 {
+            D3DTEXF_LINEAR
+        } else {
+            D3DTEXF_NONE
+        }
+    }
+
+    /// Creates an empty texture in the pool of [`upload_pool`](Self::upload_pool),
+    /// adding `usage` to its usage.
+    unsafe fn create_upload_texture(
+        &self,
+        width: u32,
+        height: u32,
+        levels: u32,
+        usage: u32,
+        format: D3DFORMAT,
+    ) -> Result<IDirect3DTexture9> {
+        let (pool_usage, pool) = self.upload_pool();
+        let mut texture: Option<IDirect3DTexture9> = None;
+        self.device.CreateTexture(
+            width,
+            height,
+            levels,
+            pool_usage | usage,
+            format,
+            pool,
+            &mut texture,
+            ptr::null_mut(),
+        )?;
+        Ok(texture.unwrap())
+    }
+
+    /// Copies an RGBA level into a level of a texture of `format`.
+    unsafe fn write_rgba_level(
+        texture: &IDirect3DTexture9,
+        i: u32,
+        level: &MipLevel,
+        format: PixelFormat,
+    ) -> Result<()> {
+        let data = pixels::from_rgba(format, &level.data);
+        let (row_len, _) = format.rows(level.width as usize, level.height as usize);
+        Self::write_rows(texture, i, &data, row_len)
+    }
+
+    /// Copies rows of pixels or DXT blocks, `row_len` bytes each, into a
+    /// level of `texture`.
+    unsafe fn write_rows(
+        texture: &IDirect3DTexture9,
+        level: u32,
+        data: &[u8],
+        row_len: usize,
+    ) -> Result<()> {
+        let mut locked_rect: D3DLOCKED_RECT = D3DLOCKED_RECT { Pitch: 0, pBits: ptr::null_mut() };
+        texture.LockRect(level, &mut locked_rect, ptr::null_mut(), 0)?;
+        copy_rows(&locked_rect, data, row_len);
+        texture.UnlockRect(level)
+    }
+
+    /// Copies tightly packed 32 bit pixels into a level of `texture`.
+    unsafe fn write_level(
+        texture: &IDirect3DTexture9,
+        level: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut locked_rect: D3DLOCKED_RECT = D3DLOCKED_RECT { Pitch: 0, pBits: ptr::null_mut() };
+        texture.LockRect(level, &mut locked_rect, ptr::null_mut(), 0)?;
+        copy_rows(&locked_rect, &data[..width as usize * height as usize * 4], width as usize * 4);
+        texture.UnlockRect(level)
+    }
+
+    /// The usage and pool of textures created from CPU data, chosen so that
+    /// they survive device resets.
+    fn upload_pool(&self) -> (u32, D3DPOOL) {
+        match self.device_ex {
+            // Ex devices do not support the managed pool, but keep their
+            // default pool resources over resets
+            Some(_) => (D3DUSAGE_DYNAMIC as u32, D3DPOOL_DEFAULT),
+            None => (0, D3DPOOL_MANAGED),
+        }
+    }
+
+    /// Creates a texture from a parsed DDS file.
+    unsafe fn create_dds_texture(&self, dds: &DdsTexture) -> Result<IDirect3DBaseTexture9> {
+        let format = d3d_format(dds.format.into());
+        let (usage, pool) = self.upload_pool();
+        let levels = dds.mip_levels as usize;
+        let mut locked_rect: D3DLOCKED_RECT = D3DLOCKED_RECT { Pitch: 0, pBits: ptr::null_mut() };
+        if dds.cube {
+            let mut texture: Option<IDirect3DCubeTexture9> = None;
+            self.device.CreateCubeTexture(
+                dds.width,
+                dds.mip_levels,
+                usage,
+                format,
+                pool,
+                &mut texture,
+                ptr::null_mut(),
+            )?;
+            let texture = texture.unwrap();
+            for (i, surface) in dds.surfaces.iter().enumerate() {
+                let (face, level) = (D3DCUBEMAP_FACES((i / levels) as i32), (i % levels) as u32);
+                texture.LockRect(face, level, &mut locked_rect, ptr::null(), 0)?;
+                copy_rows(&locked_rect, &surface.data, surface.pitch);
+                texture.UnlockRect(face, level)?;
+            }
+            texture.cast()
+        } else {
+            let mut texture: Option<IDirect3DTexture9> = None;
+            self.device.CreateTexture(
+                dds.width,
+                dds.height,
+                dds.mip_levels,
+                usage,
+                format,
+                pool,
+                &mut texture,
+                ptr::null_mut(),
+            )?;
+            let texture = texture.unwrap();
+            for (level, surface) in dds.surfaces.iter().enumerate() {
+                texture.LockRect(level as u32, &mut locked_rect, ptr::null(), 0)?;
+                copy_rows(&locked_rect, &surface.data, surface.pitch);
+                texture.UnlockRect(level as u32)?;
+            }
+            texture.cast()
+        }
+    }
+}
+
+/// The size of the framebuffer `draw_data` covers in pixels,
+/// `DXGI_ERROR_INVALID_CALL` if it is empty.
+fn framebuffer_size(draw_data: &DrawData) -> Result<[u32; 2]> {
+    let size = [
+        (draw_data.display_size[0] * draw_data.framebuffer_scale[0]) as u32,
+        (draw_data.display_size[1] * draw_data.framebuffer_scale[1]) as u32,
+    ];
+    if size[0] == 0 || size[1] == 0 {
+        return Err(DXGI_ERROR_INVALID_CALL.into());
+    }
+    Ok(size)
+}
+
+/// The [`PixelFormat`] of `format`, if it can be read back.
+fn pixel_format(format: D3DFORMAT) -> Option<PixelFormat> {
+    Some(match format {
+        D3DFMT_A8R8G8B8 => PixelFormat::A8R8G8B8,
+        D3DFMT_X8R8G8B8 => PixelFormat::X8R8G8B8,
+        D3DFMT_A8B8G8R8 => PixelFormat::A8B8G8R8,
+        D3DFMT_X8B8G8R8 => PixelFormat::X8B8G8R8,
+        D3DFMT_R5G6B5 => PixelFormat::R5G6B5,
+        D3DFMT_X1R5G5B5 => PixelFormat::X1R5G5B5,
+        D3DFMT_A1R5G5B5 => PixelFormat::A1R5G5B5,
+        D3DFMT_A4R4G4B4 => PixelFormat::A4R4G4B4,
+        D3DFMT_DXT1 => PixelFormat::Dxt(DxtFormat::Dxt1),
+        D3DFMT_DXT3 => PixelFormat::Dxt(DxtFormat::Dxt3),
+        D3DFMT_DXT5 => PixelFormat::Dxt(DxtFormat::Dxt5),
+        _ => return None,
+    })
+}
+
+/// The `D3DFORMAT` of `format`, the inverse of [`pixel_format`].
+fn d3d_format(format: PixelFormat) -> D3DFORMAT {
+    match format {
+        PixelFormat::A8R8G8B8 => D3DFMT_A8R8G8B8,
+        PixelFormat::X8R8G8B8 => D3DFMT_X8R8G8B8,
+        PixelFormat::A8B8G8R8 => D3DFMT_A8B8G8R8,
+        PixelFormat::X8B8G8R8 => D3DFMT_X8B8G8R8,
+        PixelFormat::R5G6B5 => D3DFMT_R5G6B5,
+        PixelFormat::X1R5G5B5 => D3DFMT_X1R5G5B5,
+        PixelFormat::A1R5G5B5 => D3DFMT_A1R5G5B5,
+        PixelFormat::A4R4G4B4 => D3DFMT_A4R4G4B4,
+        PixelFormat::Dxt(DxtFormat::Dxt1) => D3DFMT_DXT1,
+        PixelFormat::Dxt(DxtFormat::Dxt3) => D3DFMT_DXT3,
+        PixelFormat::Dxt(DxtFormat::Dxt5) => D3DFMT_DXT5,
+    }
+}
+
+/// Copies the rows of `data`, each `row_len` bytes long, into a locked
+/// surface.
+unsafe fn copy_rows(locked_rect: &D3DLOCKED_RECT, data: &[u8], row_len: usize) {
+    let bits = locked_rect.pBits as *mut u8;
+    let pitch = locked_rect.Pitch as usize;
+    for (y, row) in data.chunks_exact(row_len).enumerate() {
+        ptr::copy_nonoverlapping(row.as_ptr(), bits.add(pitch * y), row_len);
+    }
+}
+
+/// The pixels of the placeholder texture, a magenta and black checkerboard.
+fn checkerboard() -> Vec<u8> {
+    (0..PLACEHOLDER_SIZE * PLACEHOLDER_SIZE)
+        .flat_map(|i| {
+            let (x, y) = (i % PLACEHOLDER_SIZE, i / PLACEHOLDER_SIZE);
+            if (x / PLACEHOLDER_CELL + y / PLACEHOLDER_CELL).is_multiple_of(2) {
+                [0xFF, 0x00, 0xFF, 0xFF]
+            } else {
+                [0x00, 0x00, 0x00, 0xFF]
+            }
+        })
+        .collect()
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| {
+        let code = e.raw_os_error().map_or(E_FAIL, |code| WIN32_ERROR(code as u32).to_hresult());
+        Error::new(code, e.to_string().into())
+    })
+}
+
+struct StateBackup(IDirect3DStateBlock9);
+
+impl StateBackup {
+    unsafe fn backup(device: &IDirect3DDevice9) -> Result<Self> {
+        device.CreateStateBlock(D3DSBT_ALL).map(StateBackup)
+    }
+}
+
+impl Drop for StateBackup {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.0.Apply().expect("applying state backup failed") };
+    }
+}
+
+/// Render target bindings, which are not covered by state blocks.
+pub(crate) struct RenderTargetBackup {
+    device: IDirect3DDevice9,
+    render_target: IDirect3DSurface9,
+    depth_stencil: Option<IDirect3DSurface9>,
+    viewport: D3DVIEWPORT9,
+}
+
+impl RenderTargetBackup {
+    pub(crate) unsafe fn backup(device: &IDirect3DDevice9) -> Result<Self> {
+        let mut viewport = mem::zeroed();
+        device.GetViewport(&mut viewport)?;
+        Ok(RenderTargetBackup {
+            device: device.clone(),
+            render_target: device.GetRenderTarget(0)?,
+            // fails with D3DERR_NOTFOUND if no depth stencil surface is bound
+            depth_stencil: device.GetDepthStencilSurface().ok(),
+            viewport,
+        })
+    }
+}
+
+impl Drop for RenderTargetBackup {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .SetRenderTarget(0, &self.render_target)
+                .expect("restoring render target failed");
+            self.device
+                .SetDepthStencilSurface(self.depth_stencil.as_ref())
+                .expect("restoring depth stencil surface failed");
+            self.device.SetViewport(&self.viewport).expect("restoring viewport failed");
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Validation of draw data before it reaches the device.
+
+use std::fmt;
+
+use imgui::{DrawCmd, DrawCmdParams, DrawIdx, TextureId};
+
+/// A problem found in the draw data, see [`Renderer::validate`].
+///
+/// [`Renderer::validate`]: crate::Renderer::validate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawDataError {
+    /// Index of the offending draw list.
+    pub draw_list: usize,
+    /// Index of the offending command in its draw list.
+    pub command: usize,
+    /// What is wrong with the command.
+    pub kind: DrawDataErrorKind,
+}
+
+/// The kinds of [`DrawDataError`]s.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawDataErrorKind {
+    /// The element count does not describe whole triangles.
+    PartialTriangle {
+        /// The element count of the command.
+        count: usize,
+    },
+    /// `idx_offset + count` exceeds the index buffer of the draw list.
+    IndicesOutOfBounds {
+        /// The index offset of the command.
+        idx_offset: usize,
+        /// The element count of the command.
+        count: usize,
+        /// The length of the index buffer.
+        idx_len: usize,
+    },
+    /// An index, after adding `vtx_offset`, exceeds the vertex buffer of the
+    /// draw list. Only the first such index of a command is reported.
+    VertexOutOfBounds {
+        /// The position of the index in the index buffer.
+        position: usize,
+        /// The vertex it references, including `vtx_offset`.
+        vertex: usize,
+        /// The length of the vertex buffer.
+        vtx_len: usize,
+    },
+    /// The clip rectangle contains a NaN or infinite coordinate.
+    NonFiniteClipRect([f32; 4]),
+    /// The texture is unknown to the renderer.
+    UnknownTexture(TextureId),
+}
+
+impl fmt::Display for DrawDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "draw list {} command {}: ", self.draw_list, self.command)?;
+        match self.kind {
+            DrawDataErrorKind::PartialTriangle { count } => {
+                write!(f, "element count {} is not a multiple of 3", count)
+            },
+            DrawDataErrorKind::IndicesOutOfBounds { idx_offset, count, idx_len } => write!(
+                f,
+                "indices {}..{} exceed the index buffer of length {}",
+                idx_offset,
+                idx_offset + count,
+                idx_len
+            ),
+            DrawDataErrorKind::VertexOutOfBounds { position, vertex, vtx_len } => write!(
+                f,
+                "index {} references vertex {} of a vertex buffer of length {}",
+                position, vertex, vtx_len
+            ),
+            DrawDataErrorKind::NonFiniteClipRect(rect) => {
+                write!(f, "clip rect {:?} is not finite", rect)
+            },
+            DrawDataErrorKind::UnknownTexture(id) => write!(f, "texture {} is unknown", id.id()),
+        }
+    }
+}
+
+/// Validates draw lists given as their vertex count, index buffer and
+/// commands, checking textures with `texture_exists`.
+pub(crate) fn validate_draw_lists<'a, C: IntoIterator<Item = DrawCmd>>(
+    draw_lists: impl IntoIterator<Item = (usize, &'a [DrawIdx], C)>,
+    mut texture_exists: impl FnMut(TextureId) -> bool,
+) -> Vec<DrawDataError> {
+    let mut errors = Vec::new();
+    for (draw_list, (vtx_len, indices, commands)) in draw_lists.into_iter().enumerate() {
+        for (command, cmd) in commands.into_iter().enumerate() {
+            let (count, DrawCmdParams { clip_rect, texture_id, vtx_offset, idx_offset }) = match cmd
+            {
+                DrawCmd::Elements { count, cmd_params } => (count, cmd_params),
+                _ => continue,
+            };
+            let mut report = |kind| errors.push(DrawDataError { draw_list, command, kind });
+            if count % 3 != 0 {
+                report(DrawDataErrorKind::PartialTriangle { count });
+            }
+            match indices.get(idx_offset..).and_then(|indices| indices.get(..count)) {
+                Some(cmd_indices) => {
+                    let out_of_bounds = cmd_indices.iter().enumerate().find_map(|(i, &index)| {
+                        let vertex = vtx_offset + index as usize;
+                        (vertex >= vtx_len).then_some((idx_offset + i, vertex))
+                    });
+                    if let Some((position, vertex)) = out_of_bounds {
+                        report(DrawDataErrorKind::VertexOutOfBounds { position, vertex, vtx_len });
+                    }
+                },
+                None => report(DrawDataErrorKind::IndicesOutOfBounds {
+                    idx_offset,
+                    count,
+                    idx_len: indices.len(),
+                }),
+            }
+            if !clip_rect.iter().all(|c| c.is_finite()) {
+                report(DrawDataErrorKind::NonFiniteClipRect(clip_rect));
+            }
+            if !texture_exists(texture_id) {
+                report(DrawDataErrorKind::UnknownTexture(texture_id));
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: TextureId = TextureId::new(1);
+
+    fn elements(count: usize, idx_offset: usize, vtx_offset: usize) -> DrawCmd {
+        DrawCmd::Elements {
+            count,
+            cmd_params: DrawCmdParams {
+                clip_rect: [0.0, 0.0, 100.0, 100.0],
+                texture_id: FONT,
+                vtx_offset,
+                idx_offset,
+            },
+        }
+    }
+
+    fn validate(vtx_len: usize, indices: &[DrawIdx], commands: Vec<DrawCmd>) -> Vec<DrawDataError> {
+        validate_draw_lists([(vtx_len, indices, commands)], |id| id == FONT)
+    }
+
+    fn kinds(errors: Vec<DrawDataError>) -> Vec<DrawDataErrorKind> {
+        errors.into_iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn accepts_valid_draw_lists() {
+        let indices = [0, 1, 2, 0, 2, 3, 0, 1, 2];
+        let commands = vec![elements(6, 0, 0), DrawCmd::ResetRenderState, elements(3, 6, 4)];
+        assert!(validate(7, &indices, commands).is_empty());
+        assert!(validate_draw_lists(Vec::<(usize, &[DrawIdx], Vec<DrawCmd>)>::new(), |_| false)
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_partial_triangles() {
+        let errors = validate(3, &[0, 1, 2, 0], vec![elements(3, 0, 0), elements(1, 3, 0)]);
+        assert_eq!(
+            errors,
+            [DrawDataError {
+                draw_list: 0,
+                command: 1,
+                kind: DrawDataErrorKind::PartialTriangle { count: 1 },
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_index_ranges_exceeding_the_index_buffer() {
+        assert_eq!(
+            kinds(validate(3, &[0, 1, 2], vec![elements(6, 0, 0), elements(3, 9, 0)])),
+            [
+                DrawDataErrorKind::IndicesOutOfBounds { idx_offset: 0, count: 6, idx_len: 3 },
+                DrawDataErrorKind::IndicesOutOfBounds { idx_offset: 9, count: 3, idx_len: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_vertices_out_of_bounds_after_vtx_offset() {
+        let indices = [0, 1, 2, 0, 1, 5];
+        assert_eq!(
+            kinds(validate(3, &indices, vec![elements(3, 0, 1), elements(3, 3, 0)])),
+            [
+                DrawDataErrorKind::VertexOutOfBounds { position: 2, vertex: 3, vtx_len: 3 },
+                DrawDataErrorKind::VertexOutOfBounds { position: 5, vertex: 5, vtx_len: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_clip_rects_and_textures_per_draw_list() {
+        let bad = DrawCmd::Elements {
+            count: 3,
+            cmd_params: DrawCmdParams {
+                clip_rect: [0.0, f32::NAN, 10.0, f32::INFINITY],
+                texture_id: TextureId::new(7),
+                vtx_offset: 0,
+                idx_offset: 0,
+            },
+        };
+        let indices: &[DrawIdx] = &[0, 1, 2];
+        let errors = validate_draw_lists(
+            [(3, indices, vec![elements(3, 0, 0)]), (3, indices, vec![elements(3, 0, 0), bad])],
+            |id| id == FONT,
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.draw_list == 1 && e.command == 1));
+        assert!(matches!(errors[0].kind, DrawDataErrorKind::NonFiniteClipRect(_)));
+        assert_eq!(errors[1].kind, DrawDataErrorKind::UnknownTexture(TextureId::new(7)));
+        assert_eq!(errors[1].to_string(), "draw list 1 command 1: texture 7 is unknown");
+    }
+}
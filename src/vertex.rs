@@ -0,0 +1,186 @@
+//! Conversion of imgui's [`DrawVert`]s into the vertex layout expected by
+//! the fixed function pipeline.
+
+use imgui::DrawVert;
+
+/// A vertex in `D3DFVF_XYZ | D3DFVF_DIFFUSE | D3DFVF_TEX1` layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct CustomVertex {
+    pub(crate) pos: [f32; 3],
+    pub(crate) col: [u8; 4],
+    pub(crate) uv: [f32; 2],
+}
+
+/// Converts `src` into `dst`, widening the position to 3 components and
+/// swizzling the color from RGBA into D3DCOLOR (BGRA) order.
+///
+/// Only `min(src.len(), dst.len())` vertices are converted.
+pub(crate) fn convert_vertices(src: &[DrawVert], dst: &mut [CustomVertex]) {
+    #[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2")))]
+    {
+        sse2::convert_vertices(src, dst)
+    }
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2"))))]
+    {
+        convert_vertices_scalar(src, dst)
+    }
+}
+
+/// The portable version of [`convert_vertices`].
+pub(crate) fn convert_vertices_scalar(src: &[DrawVert], dst: &mut [CustomVertex]) {
+    for (vertex, vtx_dst) in src.iter().zip(dst.iter_mut()) {
+        *vtx_dst = CustomVertex {
+            pos: [vertex.pos[0], vertex.pos[1], 0.0],
+            col: [vertex.col[2], vertex.col[1], vertex.col[0], vertex.col[3]],
+            uv: [vertex.uv[0], vertex.uv[1]],
+        };
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2")))]
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+    use std::mem;
+
+    use imgui::DrawVert;
+
+    use super::{convert_vertices_scalar, CustomVertex};
+
+    const _: () = assert!(mem::size_of::<DrawVert>() == 20);
+    const _: () = assert!(mem::size_of::<CustomVertex>() == 24);
+
+    /// Immediate for `_mm_shuffle_ps` picking lanes `a0` and `a1` of the first
+    /// and `b0` and `b1` of the second operand.
+    const fn shuffle(a0: i32, a1: i32, b0: i32, b1: i32) -> i32 {
+        (b1 << 6) | (b0 << 4) | (a1 << 2) | a0
+    }
+
+    /// Converts vertices in blocks of four, five input lanes of 16 bytes each
+    /// map to six output lanes:
+    ///
+    /// ```text
+    /// [x0 y0 u0 v0] [c0 x1 y1 u1] [v1 c1 x2 y2] [u2 v2 c2 x3] [y3 u3 v3 c3]
+    /// [x0 y0 0 c0] [u0 v0 x1 y1] [0 c1 u1 v1] [x2 y2 0 c2] [u2 v2 x3 y3] [0 c3 u3 v3]
+    /// ```
+    pub(super) fn convert_vertices(src: &[DrawVert], dst: &mut [CustomVertex]) {
+        let len = src.len().min(dst.len());
+        let (src, dst) = (&src[..len], &mut dst[..len]);
+        let mut src_blocks = src.chunks_exact(4);
+        let mut dst_blocks = dst.chunks_exact_mut(4);
+        for (src, dst) in (&mut src_blocks).zip(&mut dst_blocks) {
+            // SAFETY: both blocks hold exactly four vertices, which are 80 and 96
+            // bytes respectively, all accesses are unaligned.
+            unsafe {
+                let src = src.as_ptr() as *const f32;
+                let dst = dst.as_mut_ptr() as *mut f32;
+                convert_block(src, dst);
+            }
+        }
+        convert_vertices_scalar(src_blocks.remainder(), dst_blocks.into_remainder());
+    }
+
+    #[inline(always)]
+    unsafe fn convert_block(src: *const f32, dst: *mut f32) {
+        let l0 = _mm_loadu_ps(src);
+        let l1 = _mm_loadu_ps(src.add(4));
+        let l2 = _mm_loadu_ps(src.add(8));
+        let l3 = _mm_loadu_ps(src.add(12));
+        let l4 = _mm_loadu_ps(src.add(16));
+
+        // gather [c0 c1 c2 c3] and swap the red and blue channels
+        let c01 = _mm_shuffle_ps::<{ shuffle(0, 0, 1, 1) }>(l1, l2);
+        let c23 = _mm_shuffle_ps::<{ shuffle(2, 2, 3, 3) }>(l3, l4);
+        let col = _mm_castps_si128(_mm_shuffle_ps::<{ shuffle(0, 2, 0, 2) }>(c01, c23));
+        let col = _mm_or_si128(
+            _mm_and_si128(col, _mm_set1_epi32(0xFF00_FF00_u32 as i32)),
+            _mm_or_si128(
+                _mm_and_si128(_mm_srli_epi32::<16>(col), _mm_set1_epi32(0x0000_00FF)),
+                _mm_and_si128(_mm_slli_epi32::<16>(col), _mm_set1_epi32(0x00FF_0000)),
+            ),
+        );
+        let zero = _mm_setzero_si128();
+        // [0 c0 0 c1] and [0 c2 0 c3]
+        let zc01 = _mm_castsi128_ps(_mm_unpacklo_epi32(zero, col));
+        let zc23 = _mm_castsi128_ps(_mm_unpackhi_epi32(zero, col));
+
+        let uv1 = _mm_shuffle_ps::<{ shuffle(3, 3, 0, 0) }>(l1, l2);
+        let xy3 = _mm_shuffle_ps::<{ shuffle(3, 3, 0, 0) }>(l3, l4);
+
+        _mm_storeu_ps(dst, _mm_shuffle_ps::<{ shuffle(0, 1, 0, 1) }>(l0, zc01));
+        _mm_storeu_ps(dst.add(4), _mm_shuffle_ps::<{ shuffle(2, 3, 1, 2) }>(l0, l1));
+        _mm_storeu_ps(dst.add(8), _mm_shuffle_ps::<{ shuffle(2, 3, 0, 2) }>(zc01, uv1));
+        _mm_storeu_ps(dst.add(12), _mm_shuffle_ps::<{ shuffle(2, 3, 0, 1) }>(l2, zc23));
+        _mm_storeu_ps(dst.add(16), _mm_shuffle_ps::<{ shuffle(0, 1, 0, 2) }>(l3, xy3));
+        _mm_storeu_ps(dst.add(20), _mm_shuffle_ps::<{ shuffle(2, 3, 1, 2) }>(zc23, l4));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use imgui::DrawVert;
+
+    use super::*;
+
+    fn vertices(len: usize) -> Vec<DrawVert> {
+        (0..len as u32)
+            .map(|i| DrawVert {
+                pos: [i as f32 * 1.5, -(i as f32) * 0.25],
+                uv: [f32::from_bits(0x3F00_0000 ^ i), f32::from_bits(0x7FC0_0000 | i)],
+                col: (i.wrapping_mul(0x9E37_79B9)).to_le_bytes(),
+            })
+            .collect()
+    }
+
+    fn bits(vertices: &[CustomVertex]) -> Vec<u32> {
+        vertices
+            .iter()
+            .flat_map(|v| {
+                [
+                    v.pos[0].to_bits(),
+                    v.pos[1].to_bits(),
+                    v.pos[2].to_bits(),
+                    u32::from_le_bytes(v.col),
+                    v.uv[0].to_bits(),
+                    v.uv[1].to_bits(),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scalar_swizzles_color_and_widens_pos() {
+        let src = [DrawVert { pos: [1.0, 2.0], uv: [3.0, 4.0], col: [0x11, 0x22, 0x33, 0x44] }];
+        let mut dst = [CustomVertex::default()];
+        convert_vertices_scalar(&src, &mut dst);
+        assert_eq!(
+            dst[0],
+            CustomVertex { pos: [1.0, 2.0, 0.0], col: [0x33, 0x22, 0x11, 0x44], uv: [3.0, 4.0] }
+        );
+    }
+
+    #[test]
+    fn matches_scalar_bit_exact() {
+        for len in 0..=37 {
+            let src = vertices(len);
+            let mut expected = vec![CustomVertex::default(); len];
+            let mut actual = vec![CustomVertex::default(); len];
+            convert_vertices_scalar(&src, &mut expected);
+            convert_vertices(&src, &mut actual);
+            assert_eq!(bits(&expected), bits(&actual), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn converts_only_the_shorter_length() {
+        let src = vertices(9);
+        let mut dst = vec![CustomVertex::default(); 6];
+        convert_vertices(&src, &mut dst);
+        let mut expected = vec![CustomVertex::default(); 6];
+        convert_vertices_scalar(&src, &mut expected);
+        assert_eq!(bits(&dst), bits(&expected));
+    }
+}
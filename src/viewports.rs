@@ -0,0 +1,194 @@
+//! Rendering of imgui's secondary platform viewports into additional swap
+//! chains.
+
+use std::{cell::RefCell, mem, ptr, rc::Rc};
+
+use imgui::{BackendFlags, Context, RendererViewportBackend, Viewport, ViewportFlags};
+use windows::Win32::Foundation::{BOOL, HWND};
+use windows::Win32::Graphics::Direct3D9::{
+    IDirect3DSwapChain9, D3DBACKBUFFER_TYPE_MONO, D3DFMT_UNKNOWN, D3DPRESENT_INTERVAL_IMMEDIATE,
+    D3DPRESENT_PARAMETERS, D3DSWAPEFFECT_DISCARD,
+};
+use windows::Win32::System::SystemServices::D3DCLEAR_TARGET;
+
+use crate::renderer::RenderTargetBackup;
+use crate::{Renderer, Result};
+
+/// Renders the secondary viewports of a multi-viewport imgui [`Context`] with
+/// a shared [`Renderer`].
+///
+/// Every platform window gets its own swap chain created with
+/// `CreateAdditionalSwapChain`, while the font texture, the textures registry
+/// and the vertex and index buffers are those of the shared renderer. The main
+/// viewport is still rendered with [`Renderer::render`].
+///
+/// Errors while rendering or presenting a viewport can not be reported through
+/// imgui's callbacks, so the affected viewport skips that frame and the error
+/// is kept for [`Renderer::take_viewport_error`].
+///
+/// Every viewport is rendered in a scene of its own, so the platform windows
+/// have to be rendered outside of the host's `BeginScene` and `EndScene`,
+/// after the main viewport:
+///
+/// ```text
+/// device.BeginScene();
+/// renderer.borrow_mut().render(ctx.render());
+/// device.EndScene();
+/// device.Present(..);
+/// ctx.update_platform_windows();
+/// ctx.render_platform_windows_default();
+/// renderer.borrow_mut().end_frame();
+/// ```
+///
+/// The shared renderer must not be borrowed while imgui renders the platform
+/// windows. The swap chains are owned by the shared renderer, which releases
+/// them in [`Renderer::invalidate_device_objects`] so that the device can be
+/// reset, and recreates them when the viewports are rendered next.
+pub struct ViewportRenderer {
+    renderer: Rc<RefCell<Renderer>>,
+}
+
+/// Per viewport data, stored in [`Viewport::renderer_user_data`].
+struct ViewportData {
+    hwnd: HWND,
+    size: [f32; 2],
+}
+
+impl ViewportRenderer {
+    /// Installs a [`ViewportRenderer`] sharing the resources of `renderer`
+    /// into `ctx` and announces viewport support to imgui.
+    ///
+    /// A platform backend creating the windows, for example one based on
+    /// [`PlatformViewportBackend`], has to be installed as well.
+    ///
+    /// [`PlatformViewportBackend`]: https://docs.rs/imgui/*/imgui/trait.PlatformViewportBackend.html
+    pub fn install(ctx: &mut Context, renderer: Rc<RefCell<Renderer>>) {
+        ctx.io_mut().backend_flags |= BackendFlags::RENDERER_HAS_VIEWPORTS;
+        ctx.set_renderer_backend(ViewportRenderer { renderer });
+    }
+
+    /// The swap chain of the window `data` describes, created if the renderer
+    /// has none for it.
+    unsafe fn swap_chain(&self, data: &ViewportData) -> Result<IDirect3DSwapChain9> {
+        if let Some(swap_chain) = self.renderer.borrow().swap_chains.get(&data.hwnd.0) {
+            return Ok(swap_chain.clone());
+        }
+        let swap_chain = self.create_swap_chain(data.hwnd, data.size)?;
+        self.renderer.borrow_mut().swap_chains.insert(data.hwnd.0, swap_chain.clone());
+        Ok(swap_chain)
+    }
+
+    unsafe fn create_swap_chain(&self, hwnd: HWND, size: [f32; 2]) -> Result<IDirect3DSwapChain9> {
+        let mut present_params = D3DPRESENT_PARAMETERS {
+            Windowed: BOOL(1),
+            SwapEffect: D3DSWAPEFFECT_DISCARD,
+            BackBufferWidth: size[0] as u32,
+            BackBufferHeight: size[1] as u32,
+            BackBufferFormat: D3DFMT_UNKNOWN,
+            hDeviceWindow: hwnd,
+            EnableAutoDepthStencil: BOOL(0),
+            PresentationInterval: D3DPRESENT_INTERVAL_IMMEDIATE as u32,
+            ..mem::zeroed()
+        };
+        let mut swap_chain = None;
+        self.renderer
+            .borrow()
+            .device()
+            .CreateAdditionalSwapChain(&mut present_params, &mut swap_chain)?;
+        Ok(swap_chain.unwrap())
+    }
+
+    /// Keeps `result`'s error for [`Renderer::take_viewport_error`].
+    fn report(&self, result: Result<()>) {
+        if let Err(e) = result {
+            self.renderer.borrow_mut().viewport_error = Some(e);
+        }
+    }
+
+    /// Releases the swap chain of the window `hwnd`.
+    fn release_swap_chain(&self, hwnd: HWND) {
+        self.renderer.borrow_mut().swap_chains.remove(&hwnd.0);
+    }
+
+    unsafe fn render_viewport(
+        &self,
+        viewport: &Viewport,
+        swap_chain: &IDirect3DSwapChain9,
+    ) -> Result<()> {
+        let mut renderer = self.renderer.borrow_mut();
+        let device = renderer.device().clone();
+        let back_buffer = swap_chain.GetBackBuffer(0, D3DBACKBUFFER_TYPE_MONO)?;
+
+        let _target_guard = RenderTargetBackup::backup(&device)?;
+        device.SetRenderTarget(0, &back_buffer)?;
+        // the main depth stencil surface may be smaller than the back buffer
+        device.SetDepthStencilSurface(None)?;
+        if !viewport.flags.contains(ViewportFlags::NO_RENDERER_CLEAR) {
+            device.Clear(0, ptr::null(), D3DCLEAR_TARGET as u32, 0xFF00_0000, 1.0, 0)?;
+        }
+        device.BeginScene()?;
+        let result = renderer.render(viewport.draw_data());
+        device.EndScene()?;
+        result
+    }
+}
+
+fn viewport_data(viewport: &mut Viewport) -> Option<&mut ViewportData> {
+    // SAFETY: renderer_user_data is either null or set by `create_window`
+    unsafe { (viewport.renderer_user_data as *mut ViewportData).as_mut() }
+}
+
+impl RendererViewportBackend for ViewportRenderer {
+    fn create_window(&mut self, viewport: &mut Viewport) {
+        // PlatformHandleRaw is the HWND, should the platform backend set it
+        let handle = if viewport.platform_handle_raw.is_null() {
+            viewport.platform_handle
+        } else {
+            viewport.platform_handle_raw
+        };
+        let data = Box::new(ViewportData { hwnd: HWND(handle as isize), size: viewport.size });
+        // the swap chain is created when the window is rendered first
+        viewport.renderer_user_data = Box::into_raw(data).cast();
+    }
+
+    fn destroy_window(&mut self, viewport: &mut Viewport) {
+        if !viewport.renderer_user_data.is_null() {
+            // SAFETY: created from a Box in `create_window`
+            let data = unsafe { Box::from_raw(viewport.renderer_user_data as *mut ViewportData) };
+            self.release_swap_chain(data.hwnd);
+        }
+        viewport.renderer_user_data = ptr::null_mut();
+    }
+
+    fn set_window_size(&mut self, viewport: &mut Viewport, size: [f32; 2]) {
+        if let Some(data) = viewport_data(viewport) {
+            // the new swap chain is created on the next render
+            data.size = size;
+            let hwnd = data.hwnd;
+            self.release_swap_chain(hwnd);
+        }
+    }
+
+    fn render_window(&mut self, viewport: &mut Viewport) {
+        let swap_chain = match viewport_data(viewport) {
+            Some(data) => unsafe { self.swap_chain(data) },
+            None => return,
+        };
+        let result = swap_chain
+            .and_then(|swap_chain| unsafe { self.render_viewport(viewport, &swap_chain) });
+        self.report(result);
+    }
+
+    fn swap_buffers(&mut self, viewport: &mut Viewport) {
+        let hwnd = match viewport_data(viewport) {
+            Some(data) => data.hwnd,
+            None => return,
+        };
+        let swap_chain = self.renderer.borrow().swap_chains.get(&hwnd.0).cloned();
+        if let Some(swap_chain) = swap_chain {
+            let result =
+                unsafe { swap_chain.Present(ptr::null(), ptr::null(), None, ptr::null(), 0) };
+            self.report(result);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! imgui clipboard access through the Win32 clipboard.
+
+/// Encodes `text` as the NUL terminated UTF-16 the clipboard's
+/// `CF_UNICODETEXT` format holds, with Windows line endings.
+pub(crate) fn to_clipboard_text(text: &str) -> Vec<u16> {
+    let mut units = Vec::with_capacity(text.len() + 1);
+    let mut prev = None;
+    for c in text.chars() {
+        if c == '\n' && prev != Some('\r') {
+            units.push(u16::from(b'\r'));
+        }
+        let mut buf = [0; 2];
+        units.extend_from_slice(c.encode_utf16(&mut buf));
+        prev = Some(c);
+    }
+    units.push(0);
+    units
+}
+
+/// Decodes `CF_UNICODETEXT` clipboard contents up to the first NUL, replacing
+/// unpaired surrogates and turning Windows line endings into `\n`.
+pub(crate) fn from_clipboard_text(units: &[u16]) -> String {
+    let len = units.iter().position(|&c| c == 0).unwrap_or(units.len());
+    String::from_utf16_lossy(&units[..len]).replace("\r\n", "\n")
+}
+
+#[cfg(windows)]
+pub use self::win32::Win32Clipboard;
+
+#[cfg(windows)]
+mod win32 {
+    use std::{ptr, slice};
+
+    use imgui::ClipboardBackend;
+    use windows::Win32::Foundation::{HANDLE, HGLOBAL, HWND};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{
+        GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    };
+
+    use super::{from_clipboard_text, to_clipboard_text};
+
+    // lives in the much larger Win32_System_Ole feature of the windows crate
+    const CF_UNICODETEXT: u32 = 13;
+
+    /// A [`ClipboardBackend`] reading and writing the Win32 clipboard as
+    /// Unicode text.
+    pub struct Win32Clipboard {
+        hwnd: HWND,
+    }
+
+    impl Win32Clipboard {
+        /// Creates a clipboard backend that opens the clipboard on behalf of
+        /// `hwnd`.
+        pub fn new(hwnd: HWND) -> Self {
+            Win32Clipboard { hwnd }
+        }
+    }
+
+    /// Keeps the clipboard open while alive.
+    struct OpenGuard;
+
+    impl OpenGuard {
+        unsafe fn open(hwnd: HWND) -> Option<Self> {
+            OpenClipboard(hwnd).as_bool().then_some(OpenGuard)
+        }
+    }
+
+    impl Drop for OpenGuard {
+        fn drop(&mut self) {
+            unsafe { CloseClipboard() };
+        }
+    }
+
+    impl ClipboardBackend for Win32Clipboard {
+        fn get(&mut self) -> Option<String> {
+            unsafe {
+                let _guard = OpenGuard::open(self.hwnd)?;
+                let data = HGLOBAL(GetClipboardData(CF_UNICODETEXT).ok()?.0);
+                let ptr = GlobalLock(data) as *const u16;
+                if ptr.is_null() {
+                    return None;
+                }
+                let units = slice::from_raw_parts(ptr, GlobalSize(data) / 2);
+                let text = from_clipboard_text(units);
+                GlobalUnlock(data);
+                Some(text)
+            }
+        }
+
+        fn set(&mut self, value: &str) {
+            let units = to_clipboard_text(value);
+            unsafe {
+                let _guard = match OpenGuard::open(self.hwnd) {
+                    Some(guard) => guard,
+                    None => return,
+                };
+                let data = match GlobalAlloc(GMEM_MOVEABLE, units.len() * 2) {
+                    Ok(data) => data,
+                    Err(_) => return,
+                };
+                let ptr = GlobalLock(data) as *mut u16;
+                if ptr.is_null() {
+                    let _ = GlobalFree(data);
+                    return;
+                }
+                ptr::copy_nonoverlapping(units.as_ptr(), ptr, units.len());
+                GlobalUnlock(data);
+                EmptyClipboard();
+                // the clipboard owns the memory once set
+                if SetClipboardData(CF_UNICODETEXT, HANDLE(data.0)).is_err() {
+                    let _ = GlobalFree(data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn encodes_with_crlf_and_nul() {
+        assert_eq!(to_clipboard_text(""), [0]);
+        let mut expected = utf16("a\r\nb\r\n\r\nc");
+        expected.push(0);
+        assert_eq!(to_clipboard_text("a\nb\r\n\nc"), expected);
+    }
+
+    #[test]
+    fn encodes_surrogate_pairs() {
+        assert_eq!(to_clipboard_text("ß😀"), [0x00DF, 0xD83D, 0xDE00, 0]);
+    }
+
+    #[test]
+    fn decodes_up_to_nul() {
+        let mut units = utf16("line 1\r\nline 2");
+        units.extend_from_slice(&[0, 0x41, 0x42]);
+        assert_eq!(from_clipboard_text(&units), "line 1\nline 2");
+        // clipboard memory may be unterminated
+        assert_eq!(from_clipboard_text(&utf16("no nul")), "no nul");
+    }
+
+    #[test]
+    fn decodes_lossily() {
+        assert_eq!(from_clipboard_text(&[0x48, 0xD800, 0x49, 0]), "H\u{FFFD}I");
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = "日本語\ttext\nwith 😀 emoji\n";
+        assert_eq!(from_clipboard_text(&to_clipboard_text(text)), text);
+    }
+}
@@ -0,0 +1,44 @@
+//! Mapping of imgui's mouse cursors to the system cursors.
+
+use imgui::MouseCursor;
+use windows::core::PCWSTR;
+use windows::Win32::UI::WindowsAndMessaging::{
+    IDC_ARROW, IDC_HAND, IDC_IBEAM, IDC_NO, IDC_SIZEALL, IDC_SIZENESW, IDC_SIZENS, IDC_SIZENWSE,
+    IDC_SIZEWE,
+};
+
+/// The system cursor resource for imgui's `cursor`, `None` if the cursor has
+/// to be hidden because imgui draws it itself or requested no cursor at all.
+pub(crate) fn cursor_resource(cursor: Option<MouseCursor>, draw_cursor: bool) -> Option<PCWSTR> {
+    let resource = match cursor? {
+        _ if draw_cursor => return None,
+        MouseCursor::Arrow => IDC_ARROW,
+        MouseCursor::TextInput => IDC_IBEAM,
+        MouseCursor::ResizeAll => IDC_SIZEALL,
+        MouseCursor::ResizeNS => IDC_SIZENS,
+        MouseCursor::ResizeEW => IDC_SIZEWE,
+        MouseCursor::ResizeNESW => IDC_SIZENESW,
+        MouseCursor::ResizeNWSE => IDC_SIZENWSE,
+        MouseCursor::Hand => IDC_HAND,
+        MouseCursor::NotAllowed => IDC_NO,
+    };
+    Some(resource)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_cursors() {
+        assert_eq!(cursor_resource(Some(MouseCursor::TextInput), false), Some(IDC_IBEAM));
+        assert_eq!(cursor_resource(Some(MouseCursor::ResizeNWSE), false), Some(IDC_SIZENWSE));
+        assert_eq!(cursor_resource(Some(MouseCursor::NotAllowed), false), Some(IDC_NO));
+    }
+
+    #[test]
+    fn hides_cursor() {
+        assert_eq!(cursor_resource(None, false), None);
+        assert_eq!(cursor_resource(Some(MouseCursor::Arrow), true), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Input method editor support, placing the composition and candidate windows
+//! at imgui's text cursor and fetching composition results.
+
+use windows::Win32::Foundation::{POINT, RECT};
+
+/// The IME window placement for imgui's text cursor, in client coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ImePlacement {
+    /// Top left corner of the composition window and the candidate list.
+    pub(crate) pos: POINT,
+    /// The text line the candidate list must not cover.
+    pub(crate) exclude: RECT,
+}
+
+/// Computes the placement for an input cursor at `input_pos` with the given
+/// line height, both in imgui coordinates of a viewport positioned at
+/// `display_pos`. The latter is the `display_pos` of the viewport's draw data,
+/// which the renderer maps to the top left corner of the client area.
+pub(crate) fn ime_placement(
+    input_pos: [f32; 2],
+    line_height: f32,
+    display_pos: [f32; 2],
+) -> ImePlacement {
+    let x = (input_pos[0] - display_pos[0]) as i32;
+    let y = (input_pos[1] - display_pos[1]) as i32;
+    ImePlacement {
+        pos: POINT { x, y },
+        exclude: RECT { left: x, top: y, right: x + 1, bottom: y + line_height.ceil() as i32 },
+    }
+}
+
+#[cfg(windows)]
+pub(super) use self::win32::{composition_result, set_platform_ime_data};
+
+#[cfg(windows)]
+mod win32 {
+    use imgui::sys;
+    use windows::Win32::Foundation::{HWND, RECT};
+    use windows::Win32::UI::Input::Ime::{
+        ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, ImmSetCandidateWindow,
+        ImmSetCompositionWindow, CANDIDATEFORM, CFS_EXCLUDE, CFS_FORCE_POSITION, COMPOSITIONFORM,
+        GCS_RESULTSTR,
+    };
+
+    use super::ime_placement;
+
+    /// imgui's `SetPlatformImeDataFn`, moving the IME windows of the
+    /// viewport's window to the text cursor.
+    pub(in crate::win32) unsafe extern "C" fn set_platform_ime_data(
+        viewport: *mut sys::ImGuiViewport,
+        data: *mut sys::ImGuiPlatformImeData,
+    ) {
+        let (viewport, data) = (&*viewport, &*data);
+        let hwnd = HWND(viewport.PlatformHandleRaw as isize);
+        if hwnd.0 == 0 || !data.WantVisible {
+            return;
+        }
+        let himc = ImmGetContext(hwnd);
+        if himc.0 == 0 {
+            return;
+        }
+        let placement = ime_placement(
+            [data.InputPos.x, data.InputPos.y],
+            data.InputLineHeight,
+            [viewport.Pos.x, viewport.Pos.y],
+        );
+        let composition = COMPOSITIONFORM {
+            dwStyle: CFS_FORCE_POSITION,
+            ptCurrentPos: placement.pos,
+            rcArea: RECT::default(),
+        };
+        ImmSetCompositionWindow(himc, &composition);
+        let candidate = CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: CFS_EXCLUDE,
+            ptCurrentPos: placement.pos,
+            rcArea: placement.exclude,
+        };
+        ImmSetCandidateWindow(himc, &candidate);
+        ImmReleaseContext(hwnd, himc);
+    }
+
+    /// Takes the result string of the finished composition of `hwnd`'s input
+    /// context as UTF-16.
+    pub(in crate::win32) unsafe fn composition_result(hwnd: HWND) -> Vec<u16> {
+        let himc = ImmGetContext(hwnd);
+        if himc.0 == 0 {
+            return Vec::new();
+        }
+        // the length is in bytes
+        let len = ImmGetCompositionStringW(himc, GCS_RESULTSTR, None, 0).max(0) as usize;
+        let mut text = vec![0u16; len / 2];
+        if !text.is_empty() {
+            let written = ImmGetCompositionStringW(
+                himc,
+                GCS_RESULTSTR,
+                Some(text.as_mut_ptr().cast()),
+                len as u32,
+            );
+            text.truncate(written.max(0) as usize / 2);
+        }
+        ImmReleaseContext(hwnd, himc);
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_windows_relative_to_display_pos() {
+        let placement = ime_placement([150.5, 220.0], 13.5, [100.0, 200.0]);
+        assert_eq!(placement.pos, POINT { x: 50, y: 20 });
+        assert_eq!(placement.exclude, RECT { left: 50, top: 20, right: 51, bottom: 34 });
+    }
+
+    #[test]
+    fn places_windows_of_secondary_viewports() {
+        // viewports of other monitors can sit at negative screen coordinates
+        let placement = ime_placement([-1800.0, 40.0], 16.0, [-1920.0, 0.0]);
+        assert_eq!(placement.pos, POINT { x: 120, y: 40 });
+        assert_eq!(placement.exclude.bottom, 56);
+    }
+}
@@ -0,0 +1,365 @@
+//! Translation of window messages into imgui input events, free of any Win32
+//! calls.
+
+use imgui::{Key, MouseButton};
+use windows::Win32::UI::Input::Ime::GCS_RESULTSTR;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::{
+    WHEEL_DELTA, WM_CHAR, WM_IME_COMPOSITION, WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDBLCLK,
+    WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL,
+    WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETFOCUS,
+    WM_SIZE, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDBLCLK, WM_XBUTTONDOWN, WM_XBUTTONUP,
+};
+
+// lives in the much larger Win32_UI_Controls feature of the windows crate
+const WM_MOUSELEAVE: u32 = 0x02A3;
+
+/// An imgui input event derived from a window message.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// A key was pressed or released. Left and right modifier keys are told
+    /// apart, the `Mod*` keys are not generated.
+    Key {
+        /// The key.
+        key: Key,
+        /// Whether the key is now held down.
+        down: bool,
+    },
+    /// A UTF-16 code unit was typed, surrogate pairs arrive as two events.
+    Char(u16),
+    /// The IME finished a composition, its result string has to be fetched
+    /// from the input context.
+    ImeResult,
+    /// The mouse moved to the given client coordinates.
+    MousePos([f32; 2]),
+    /// The mouse left the client area.
+    MouseLeave,
+    /// A mouse button was pressed or released.
+    MouseButton {
+        /// The button.
+        button: MouseButton,
+        /// Whether the button is now held down.
+        down: bool,
+    },
+    /// The mouse wheel was scrolled by the given amount of notches,
+    /// horizontally and vertically.
+    MouseWheel([f32; 2]),
+    /// The window gained or lost the keyboard focus.
+    Focus(bool),
+    /// The client area was resized to the given size.
+    Resize([f32; 2]),
+}
+
+fn loword(v: usize) -> u16 {
+    v as u16
+}
+
+fn hiword(v: usize) -> u16 {
+    (v >> 16) as u16
+}
+
+fn point(lparam: isize) -> [f32; 2] {
+    [loword(lparam as usize) as i16 as f32, hiword(lparam as usize) as i16 as f32]
+}
+
+/// Translates a window message into an [`InputEvent`], `None` if the message
+/// carries no input imgui is interested in.
+///
+/// `wparam` and `lparam` are the raw values of the window procedure's
+/// `WPARAM` and `LPARAM` arguments.
+pub fn translate_message(msg: u32, wparam: usize, lparam: isize) -> Option<InputEvent> {
+    let button = |button, down| Some(InputEvent::MouseButton { button, down });
+    let x_button = || match hiword(wparam) {
+        1 => MouseButton::Extra1,
+        _ => MouseButton::Extra2,
+    };
+    let wheel = || hiword(wparam) as i16 as f32 / WHEEL_DELTA as f32;
+    match msg {
+        WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP => {
+            let down = matches!(msg, WM_KEYDOWN | WM_SYSKEYDOWN);
+            translate_key(wparam, lparam).map(|key| InputEvent::Key { key, down })
+        },
+        WM_CHAR => (wparam > 0 && wparam < 0x1_0000).then_some(InputEvent::Char(wparam as u16)),
+        WM_IME_COMPOSITION if lparam as u32 & GCS_RESULTSTR.0 != 0 => Some(InputEvent::ImeResult),
+        WM_MOUSEMOVE => Some(InputEvent::MousePos(point(lparam))),
+        WM_MOUSELEAVE => Some(InputEvent::MouseLeave),
+        WM_LBUTTONDOWN | WM_LBUTTONDBLCLK => button(MouseButton::Left, true),
+        WM_LBUTTONUP => button(MouseButton::Left, false),
+        WM_RBUTTONDOWN | WM_RBUTTONDBLCLK => button(MouseButton::Right, true),
+        WM_RBUTTONUP => button(MouseButton::Right, false),
+        WM_MBUTTONDOWN | WM_MBUTTONDBLCLK => button(MouseButton::Middle, true),
+        WM_MBUTTONUP => button(MouseButton::Middle, false),
+        WM_XBUTTONDOWN | WM_XBUTTONDBLCLK => button(x_button(), true),
+        WM_XBUTTONUP => button(x_button(), false),
+        WM_MOUSEWHEEL => Some(InputEvent::MouseWheel([0.0, wheel()])),
+        // imgui scrolls to the left for positive values
+        WM_MOUSEHWHEEL => Some(InputEvent::MouseWheel([-wheel(), 0.0])),
+        WM_SETFOCUS => Some(InputEvent::Focus(true)),
+        WM_KILLFOCUS => Some(InputEvent::Focus(false)),
+        WM_SIZE => Some(InputEvent::Resize([
+            loword(lparam as usize) as f32,
+            hiword(lparam as usize) as f32,
+        ])),
+        _ => None,
+    }
+}
+
+/// Maps the virtual key of a key message to an imgui [`Key`], using the scan
+/// code and extended key flag in `lparam` to tell left and right apart.
+fn translate_key(wparam: usize, lparam: isize) -> Option<Key> {
+    let scan_code = (lparam >> 16) & 0xFF;
+    let extended = lparam & (1 << 24) != 0;
+    let key = match VIRTUAL_KEY(wparam as u16) {
+        VK_TAB => Key::Tab,
+        VK_LEFT => Key::LeftArrow,
+        VK_RIGHT => Key::RightArrow,
+        VK_UP => Key::UpArrow,
+        VK_DOWN => Key::DownArrow,
+        VK_PRIOR => Key::PageUp,
+        VK_NEXT => Key::PageDown,
+        VK_HOME => Key::Home,
+        VK_END => Key::End,
+        VK_INSERT => Key::Insert,
+        VK_DELETE => Key::Delete,
+        VK_BACK => Key::Backspace,
+        VK_SPACE => Key::Space,
+        VK_RETURN if extended => Key::KeypadEnter,
+        VK_RETURN => Key::Enter,
+        VK_ESCAPE => Key::Escape,
+        VK_OEM_7 => Key::Apostrophe,
+        VK_OEM_COMMA => Key::Comma,
+        VK_OEM_MINUS => Key::Minus,
+        VK_OEM_PERIOD => Key::Period,
+        VK_OEM_2 => Key::Slash,
+        VK_OEM_1 => Key::Semicolon,
+        VK_OEM_PLUS => Key::Equal,
+        VK_OEM_4 => Key::LeftBracket,
+        VK_OEM_5 => Key::Backslash,
+        VK_OEM_6 => Key::RightBracket,
+        VK_OEM_3 => Key::GraveAccent,
+        VK_CAPITAL => Key::CapsLock,
+        VK_SCROLL => Key::ScrollLock,
+        VK_NUMLOCK => Key::NumLock,
+        VK_SNAPSHOT => Key::PrintScreen,
+        VK_PAUSE => Key::Pause,
+        VK_NUMPAD0 => Key::Keypad0,
+        VK_NUMPAD1 => Key::Keypad1,
+        VK_NUMPAD2 => Key::Keypad2,
+        VK_NUMPAD3 => Key::Keypad3,
+        VK_NUMPAD4 => Key::Keypad4,
+        VK_NUMPAD5 => Key::Keypad5,
+        VK_NUMPAD6 => Key::Keypad6,
+        VK_NUMPAD7 => Key::Keypad7,
+        VK_NUMPAD8 => Key::Keypad8,
+        VK_NUMPAD9 => Key::Keypad9,
+        VK_DECIMAL => Key::KeypadDecimal,
+        VK_DIVIDE => Key::KeypadDivide,
+        VK_MULTIPLY => Key::KeypadMultiply,
+        VK_SUBTRACT => Key::KeypadSubtract,
+        VK_ADD => Key::KeypadAdd,
+        // the generic modifier keys are sent instead of the sided ones
+        VK_SHIFT if scan_code == 0x36 => Key::RightShift,
+        VK_SHIFT | VK_LSHIFT => Key::LeftShift,
+        VK_RSHIFT => Key::RightShift,
+        VK_CONTROL if extended => Key::RightCtrl,
+        VK_CONTROL | VK_LCONTROL => Key::LeftCtrl,
+        VK_RCONTROL => Key::RightCtrl,
+        VK_MENU if extended => Key::RightAlt,
+        VK_MENU | VK_LMENU => Key::LeftAlt,
+        VK_RMENU => Key::RightAlt,
+        VK_LWIN => Key::LeftSuper,
+        VK_RWIN => Key::RightSuper,
+        VK_APPS => Key::Menu,
+        VK_0 => Key::Alpha0,
+        VK_1 => Key::Alpha1,
+        VK_2 => Key::Alpha2,
+        VK_3 => Key::Alpha3,
+        VK_4 => Key::Alpha4,
+        VK_5 => Key::Alpha5,
+        VK_6 => Key::Alpha6,
+        VK_7 => Key::Alpha7,
+        VK_8 => Key::Alpha8,
+        VK_9 => Key::Alpha9,
+        VK_A => Key::A,
+        VK_B => Key::B,
+        VK_C => Key::C,
+        VK_D => Key::D,
+        VK_E => Key::E,
+        VK_F => Key::F,
+        VK_G => Key::G,
+        VK_H => Key::H,
+        VK_I => Key::I,
+        VK_J => Key::J,
+        VK_K => Key::K,
+        VK_L => Key::L,
+        VK_M => Key::M,
+        VK_N => Key::N,
+        VK_O => Key::O,
+        VK_P => Key::P,
+        VK_Q => Key::Q,
+        VK_R => Key::R,
+        VK_S => Key::S,
+        VK_T => Key::T,
+        VK_U => Key::U,
+        VK_V => Key::V,
+        VK_W => Key::W,
+        VK_X => Key::X,
+        VK_Y => Key::Y,
+        VK_Z => Key::Z,
+        VK_F1 => Key::F1,
+        VK_F2 => Key::F2,
+        VK_F3 => Key::F3,
+        VK_F4 => Key::F4,
+        VK_F5 => Key::F5,
+        VK_F6 => Key::F6,
+        VK_F7 => Key::F7,
+        VK_F8 => Key::F8,
+        VK_F9 => Key::F9,
+        VK_F10 => Key::F10,
+        VK_F11 => Key::F11,
+        VK_F12 => Key::F12,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Tracks the held left and right modifier keys to derive imgui's `Mod*`
+/// keys, which stay down as long as either side is held.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Modifiers {
+    held: u8,
+}
+
+impl Modifiers {
+    /// Records a key event, returning the `Mod*` key event to submit if the
+    /// state of the corresponding modifier changed.
+    pub(crate) fn update(&mut self, key: Key, down: bool) -> Option<(Key, bool)> {
+        let (bit, modifier) = match key {
+            Key::LeftCtrl => (0, Key::ModCtrl),
+            Key::RightCtrl => (1, Key::ModCtrl),
+            Key::LeftShift => (2, Key::ModShift),
+            Key::RightShift => (3, Key::ModShift),
+            Key::LeftAlt => (4, Key::ModAlt),
+            Key::RightAlt => (5, Key::ModAlt),
+            Key::LeftSuper => (6, Key::ModSuper),
+            Key::RightSuper => (7, Key::ModSuper),
+            _ => return None,
+        };
+        let both = 0b11 << (bit & !1);
+        let was_down = self.held & both != 0;
+        if down {
+            self.held |= 1 << bit;
+        } else {
+            self.held &= !(1 << bit);
+        }
+        let is_down = self.held & both != 0;
+        (was_down != is_down).then_some((modifier, is_down))
+    }
+
+    /// Forgets all held keys, imgui releases them itself on focus loss.
+    pub(crate) fn clear(&mut self) {
+        self.held = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_lparam(scan_code: isize, extended: bool) -> isize {
+        1 | scan_code << 16 | (extended as isize) << 24
+    }
+
+    #[test]
+    fn translates_keys() {
+        let key = |msg, vk: VIRTUAL_KEY, lparam| translate_message(msg, vk.0 as usize, lparam);
+        assert_eq!(
+            key(WM_KEYDOWN, VK_A, key_lparam(0x1E, false)),
+            Some(InputEvent::Key { key: Key::A, down: true })
+        );
+        assert_eq!(
+            key(WM_SYSKEYUP, VK_F10, key_lparam(0x44, false)),
+            Some(InputEvent::Key { key: Key::F10, down: false })
+        );
+        assert_eq!(
+            key(WM_KEYDOWN, VK_RETURN, key_lparam(0x1C, true)),
+            Some(InputEvent::Key { key: Key::KeypadEnter, down: true })
+        );
+        assert_eq!(key(WM_KEYDOWN, VK_VOLUME_UP, key_lparam(0x30, true)), None);
+    }
+
+    #[test]
+    fn tells_modifier_sides_apart() {
+        let key =
+            |vk: VIRTUAL_KEY, lparam| match translate_message(WM_KEYDOWN, vk.0 as usize, lparam) {
+                Some(InputEvent::Key { key, .. }) => key,
+                event => panic!("unexpected {:?}", event),
+            };
+        assert_eq!(key(VK_SHIFT, key_lparam(0x2A, false)), Key::LeftShift);
+        assert_eq!(key(VK_SHIFT, key_lparam(0x36, false)), Key::RightShift);
+        assert_eq!(key(VK_CONTROL, key_lparam(0x1D, false)), Key::LeftCtrl);
+        assert_eq!(key(VK_CONTROL, key_lparam(0x1D, true)), Key::RightCtrl);
+        assert_eq!(key(VK_MENU, key_lparam(0x38, false)), Key::LeftAlt);
+        assert_eq!(key(VK_MENU, key_lparam(0x38, true)), Key::RightAlt);
+        assert_eq!(key(VK_RWIN, key_lparam(0x5C, true)), Key::RightSuper);
+    }
+
+    #[test]
+    fn translates_mouse() {
+        // x = -5, y = 300 as packed by GET_X_LPARAM and GET_Y_LPARAM
+        let lparam = (300 << 16) | 0xFFFB;
+        assert_eq!(
+            translate_message(WM_MOUSEMOVE, 0, lparam),
+            Some(InputEvent::MousePos([-5.0, 300.0]))
+        );
+        assert_eq!(
+            translate_message(WM_RBUTTONDBLCLK, 0, lparam),
+            Some(InputEvent::MouseButton { button: MouseButton::Right, down: true })
+        );
+        assert_eq!(
+            translate_message(WM_XBUTTONUP, 2 << 16, lparam),
+            Some(InputEvent::MouseButton { button: MouseButton::Extra2, down: false })
+        );
+        let notches = |n: i16| ((n as u16 as usize) << 16) | 0x0008;
+        assert_eq!(
+            translate_message(WM_MOUSEWHEEL, notches(-240), 0),
+            Some(InputEvent::MouseWheel([0.0, -2.0]))
+        );
+        assert_eq!(
+            translate_message(WM_MOUSEHWHEEL, notches(120), 0),
+            Some(InputEvent::MouseWheel([-1.0, 0.0]))
+        );
+        assert_eq!(translate_message(WM_MOUSELEAVE, 0, 0), Some(InputEvent::MouseLeave));
+    }
+
+    #[test]
+    fn translates_window_messages() {
+        assert_eq!(translate_message(WM_CHAR, 0xD83D, 1), Some(InputEvent::Char(0xD83D)));
+        assert_eq!(translate_message(WM_CHAR, 0, 1), None);
+        assert_eq!(
+            translate_message(WM_IME_COMPOSITION, 0, GCS_RESULTSTR.0 as isize),
+            Some(InputEvent::ImeResult)
+        );
+        // composition updates without a result are left to the IME window
+        assert_eq!(translate_message(WM_IME_COMPOSITION, 0, 0x0008), None);
+        assert_eq!(
+            translate_message(WM_SIZE, 0, (600 << 16) | 800),
+            Some(InputEvent::Resize([800.0, 600.0]))
+        );
+        assert_eq!(translate_message(WM_KILLFOCUS, 0, 0), Some(InputEvent::Focus(false)));
+        assert_eq!(translate_message(WM_SETFOCUS, 0, 0), Some(InputEvent::Focus(true)));
+    }
+
+    #[test]
+    fn modifiers_stay_down_while_either_side_is_held() {
+        let mut modifiers = Modifiers::default();
+        assert_eq!(modifiers.update(Key::LeftCtrl, true), Some((Key::ModCtrl, true)));
+        assert_eq!(modifiers.update(Key::RightCtrl, true), None);
+        assert_eq!(modifiers.update(Key::LeftCtrl, false), None);
+        assert_eq!(modifiers.update(Key::RightShift, true), Some((Key::ModShift, true)));
+        assert_eq!(modifiers.update(Key::RightCtrl, false), Some((Key::ModCtrl, false)));
+        assert_eq!(modifiers.update(Key::A, true), None);
+        modifiers.clear();
+        assert_eq!(modifiers.update(Key::LeftShift, true), Some((Key::ModShift, true)));
+    }
+}
@@ -0,0 +1,23 @@
+//! Win32 platform integration for applications driving their own window
+//! procedure, the counterpart of imgui's `imgui_impl_win32`.
+//!
+//! [`translate_message`] holds the actual message translation and does not
+//! call into Win32, `Win32Platform` feeds its results into imgui and takes
+//! care of the IME, the mouse cursor and the clipboard.
+
+#[cfg_attr(not(windows), allow(dead_code))]
+mod clipboard;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod cursor;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod ime;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod input;
+#[cfg(windows)]
+mod platform;
+
+#[cfg(windows)]
+pub use clipboard::Win32Clipboard;
+pub use input::{translate_message, InputEvent};
+#[cfg(windows)]
+pub use platform::Win32Platform;
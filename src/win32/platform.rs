@@ -0,0 +1,198 @@
+use std::{ffi::c_void, mem, time::Instant};
+
+use imgui::internal::RawCast;
+use imgui::{sys, BackendFlags, ConfigFlags, Context, Io, Ui};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{HWND, LPARAM, RECT, WPARAM};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetCapture, ReleaseCapture, SetCapture, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetClientRect, LoadCursorW, SetCursor, HCURSOR, HTCLIENT, IDC_ARROW, WM_SETCURSOR,
+};
+
+use super::clipboard::Win32Clipboard;
+use super::cursor::cursor_resource;
+use super::ime;
+use super::input::{translate_message, InputEvent, Modifiers};
+use crate::Result;
+
+/// Feeds the input of a plain Win32 window into an imgui [`Context`].
+///
+/// Call [`handle_message`](Self::handle_message) from the window procedure,
+/// [`prepare_frame`](Self::prepare_frame) before every
+/// [`Context::new_frame`] and [`prepare_render`](Self::prepare_render) before
+/// rendering.
+pub struct Win32Platform {
+    hwnd: HWND,
+    last_frame: Instant,
+    modifiers: Modifiers,
+    mouse_buttons_down: u8,
+    mouse_tracked: bool,
+    /// The system cursor last requested by imgui, `None` if it is hidden.
+    cursor: Option<PCWSTR>,
+    /// Whether the platform controls the cursor shape.
+    set_cursor: bool,
+}
+
+impl Win32Platform {
+    /// Creates the platform for the window `hwnd` and registers it with
+    /// `ctx`, which has to be the current context.
+    ///
+    /// The IME composition and candidate windows are moved to imgui's text
+    /// cursor from then on and a [`Win32Clipboard`] is installed as the
+    /// clipboard backend.
+    pub fn init(ctx: &mut Context, hwnd: HWND) -> Self {
+        ctx.set_platform_name(String::from(concat!(
+            "imgui_dx9_renderer_win32@",
+            env!("CARGO_PKG_VERSION")
+        )));
+        ctx.io_mut().backend_flags |= BackendFlags::HAS_MOUSE_CURSORS;
+        ctx.io_mut().set_platform_ime_data_fn = Some(ime::set_platform_ime_data);
+        ctx.set_clipboard_backend(Win32Clipboard::new(hwnd));
+        // SAFETY: the main viewport of the current context lives as long as the context
+        unsafe { (*sys::igGetMainViewport()).PlatformHandleRaw = hwnd.0 as *mut c_void };
+        Win32Platform {
+            hwnd,
+            last_frame: Instant::now(),
+            modifiers: Modifiers::default(),
+            mouse_buttons_down: 0,
+            mouse_tracked: false,
+            cursor: Some(IDC_ARROW),
+            set_cursor: false,
+        }
+    }
+
+    /// The window this platform handles.
+    pub fn hwnd(&self) -> HWND {
+        self.hwnd
+    }
+
+    /// Forwards a message of the window to imgui, returning whether the
+    /// message was consumed.
+    ///
+    /// Consumed messages must not be passed on to `DefWindowProcW`, the
+    /// window procedure should return 0 for them instead. This is the case for
+    /// finished IME compositions, whose text would arrive a second time as
+    /// `WM_CHAR` messages otherwise, and for `WM_SETCURSOR` in the client
+    /// area while imgui controls the cursor shape. All other messages should still be
+    /// handled as usual, use [`Io::want_capture_mouse`] and
+    /// [`Io::want_capture_keyboard`] to decide whether the application should
+    /// react to them as well.
+    pub fn handle_message(
+        &mut self,
+        io: &mut Io,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> bool {
+        if msg == WM_SETCURSOR && lparam.0 as u32 & 0xFFFF == HTCLIENT && self.set_cursor {
+            self.apply_cursor();
+            return true;
+        }
+        let event = match translate_message(msg, wparam.0, lparam.0) {
+            Some(event) => event,
+            None => return false,
+        };
+        let mut consumed = false;
+        match event {
+            InputEvent::Key { key, down } => {
+                io.add_key_event(key, down);
+                if let Some((modifier, down)) = self.modifiers.update(key, down) {
+                    io.add_key_event(modifier, down);
+                }
+            },
+            // SAFETY: Io is layout compatible with ImGuiIO
+            InputEvent::Char(c) => unsafe { sys::ImGuiIO_AddInputCharacterUTF16(io.raw_mut(), c) },
+            InputEvent::ImeResult => {
+                for c in unsafe { ime::composition_result(self.hwnd) } {
+                    // SAFETY: Io is layout compatible with ImGuiIO
+                    unsafe { sys::ImGuiIO_AddInputCharacterUTF16(io.raw_mut(), c) };
+                }
+                consumed = true;
+            },
+            InputEvent::MousePos(pos) => {
+                if !self.mouse_tracked {
+                    let mut track = TRACKMOUSEEVENT {
+                        cbSize: mem::size_of::<TRACKMOUSEEVENT>() as u32,
+                        dwFlags: TME_LEAVE,
+                        hwndTrack: self.hwnd,
+                        dwHoverTime: 0,
+                    };
+                    self.mouse_tracked = unsafe { TrackMouseEvent(&mut track).as_bool() };
+                }
+                io.add_mouse_pos_event(pos);
+            },
+            InputEvent::MouseLeave => {
+                self.mouse_tracked = false;
+                io.add_mouse_pos_event([-f32::MAX, -f32::MAX]);
+            },
+            InputEvent::MouseButton { button, down } => {
+                // keep receiving mouse messages while a button is held outside the window
+                let bit = 1 << button as u8;
+                unsafe {
+                    if down {
+                        if self.mouse_buttons_down == 0 && GetCapture() == HWND(0) {
+                            SetCapture(self.hwnd);
+                        }
+                        self.mouse_buttons_down |= bit;
+                    } else {
+                        self.mouse_buttons_down &= !bit;
+                        if self.mouse_buttons_down == 0 && GetCapture() == self.hwnd {
+                            ReleaseCapture();
+                        }
+                    }
+                }
+                io.add_mouse_button_event(button, down);
+            },
+            InputEvent::MouseWheel(wheel) => io.add_mouse_wheel_event(wheel),
+            InputEvent::Focus(focused) => {
+                if !focused {
+                    self.modifiers.clear();
+                }
+                // SAFETY: Io is layout compatible with ImGuiIO
+                unsafe { sys::ImGuiIO_AddFocusEvent(io.raw_mut(), focused) };
+            },
+            InputEvent::Resize(size) => io.display_size = size,
+        }
+        consumed
+    }
+
+    /// Updates the display size from the window's client area and the delta
+    /// time since the last frame.
+    pub fn prepare_frame(&mut self, io: &mut Io) -> Result<()> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.hwnd, &mut rect).ok()? };
+        io.display_size = [(rect.right - rect.left) as f32, (rect.bottom - rect.top) as f32];
+        let now = Instant::now();
+        io.update_delta_time(now - self.last_frame);
+        self.last_frame = now;
+        Ok(())
+    }
+
+    /// Updates the mouse cursor to the one requested by imgui, unless
+    /// [`ConfigFlags::NO_MOUSE_CURSOR_CHANGE`] is set.
+    ///
+    /// The cursor is hidden while [`Io::mouse_draw_cursor`] is set, as imgui
+    /// draws it itself then.
+    pub fn prepare_render(&mut self, ui: &Ui) {
+        let io = ui.io();
+        self.set_cursor = !io.config_flags.contains(ConfigFlags::NO_MOUSE_CURSOR_CHANGE);
+        if !self.set_cursor {
+            return;
+        }
+        let cursor = cursor_resource(ui.mouse_cursor(), io.mouse_draw_cursor);
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            self.apply_cursor();
+        }
+    }
+
+    fn apply_cursor(&self) {
+        let cursor = self
+            .cursor
+            .and_then(|resource| unsafe { LoadCursorW(None, resource) }.ok())
+            .unwrap_or(HCURSOR(0));
+        unsafe { SetCursor(cursor) };
+    }
+}
@@ -0,0 +1,205 @@
+//! Gamepad navigation with XInput controllers.
+//!
+//! [`map_gamepad`] translates a controller state into imgui's gamepad keys
+//! without touching any controller, `XInputGamepad` polls a controller every
+//! frame and feeds the result into imgui.
+
+use imgui::Key;
+use windows::Win32::UI::Input::XboxController::*;
+
+/// The state of one of imgui's gamepad keys.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GamepadKey {
+    /// The key.
+    pub key: Key,
+    /// Whether the key counts as pressed.
+    pub down: bool,
+    /// How far the key is pressed, from 0.0 to 1.0. Digital buttons are
+    /// either 0.0 or 1.0.
+    pub value: f32,
+}
+
+const BUTTONS: [(Key, XINPUT_GAMEPAD_BUTTON_FLAGS); 14] = [
+    (Key::GamepadStart, XINPUT_GAMEPAD_START),
+    (Key::GamepadBack, XINPUT_GAMEPAD_BACK),
+    (Key::GamepadFaceLeft, XINPUT_GAMEPAD_X),
+    (Key::GamepadFaceRight, XINPUT_GAMEPAD_B),
+    (Key::GamepadFaceUp, XINPUT_GAMEPAD_Y),
+    (Key::GamepadFaceDown, XINPUT_GAMEPAD_A),
+    (Key::GamepadDpadLeft, XINPUT_GAMEPAD_DPAD_LEFT),
+    (Key::GamepadDpadRight, XINPUT_GAMEPAD_DPAD_RIGHT),
+    (Key::GamepadDpadUp, XINPUT_GAMEPAD_DPAD_UP),
+    (Key::GamepadDpadDown, XINPUT_GAMEPAD_DPAD_DOWN),
+    (Key::GamepadL1, XINPUT_GAMEPAD_LEFT_SHOULDER),
+    (Key::GamepadR1, XINPUT_GAMEPAD_RIGHT_SHOULDER),
+    (Key::GamepadL3, XINPUT_GAMEPAD_LEFT_THUMB),
+    (Key::GamepadR3, XINPUT_GAMEPAD_RIGHT_THUMB),
+];
+
+/// Analog values need to exceed this fraction of their range past the
+/// deadzone to count as pressed.
+const ANALOG_PRESS_THRESHOLD: f32 = 0.1;
+
+/// Maps `value` from the range starting at the end of the deadzone `v0` to
+/// the extreme `v1` onto 0.0 to 1.0.
+fn analog(key: Key, value: i32, v0: i32, v1: i32) -> GamepadKey {
+    let value = (value - v0) as f32 / (v1 - v0) as f32;
+    GamepadKey { key, down: value > ANALOG_PRESS_THRESHOLD, value: value.clamp(0.0, 1.0) }
+}
+
+/// Maps the state of an XInput controller to the state of all of imgui's
+/// gamepad keys.
+///
+/// Triggers and stick directions are analog, they start at the respective
+/// XInput deadzone and are split into one key per direction.
+pub fn map_gamepad(gamepad: &XINPUT_GAMEPAD) -> impl Iterator<Item = GamepadKey> + '_ {
+    let button = move |(key, flag): (Key, XINPUT_GAMEPAD_BUTTON_FLAGS)| {
+        let down = gamepad.wButtons.0 & flag.0 != 0;
+        GamepadKey { key, down, value: if down { 1.0 } else { 0.0 } }
+    };
+    let trigger = XINPUT_GAMEPAD_TRIGGER_THRESHOLD.0 as i32;
+    let left = XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE.0 as i32;
+    let right = XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE.0 as i32;
+    let (lx, ly) = (gamepad.sThumbLX as i32, gamepad.sThumbLY as i32);
+    let (rx, ry) = (gamepad.sThumbRX as i32, gamepad.sThumbRY as i32);
+    BUTTONS.into_iter().map(button).chain([
+        analog(Key::GamepadL2, gamepad.bLeftTrigger as i32, trigger, 255),
+        analog(Key::GamepadR2, gamepad.bRightTrigger as i32, trigger, 255),
+        analog(Key::GamepadLStickLeft, lx, -left, -32768),
+        analog(Key::GamepadLStickRight, lx, left, 32767),
+        analog(Key::GamepadLStickUp, ly, left, 32767),
+        analog(Key::GamepadLStickDown, ly, -left, -32768),
+        analog(Key::GamepadRStickLeft, rx, -right, -32768),
+        analog(Key::GamepadRStickRight, rx, right, 32767),
+        analog(Key::GamepadRStickUp, ry, right, 32767),
+        analog(Key::GamepadRStickDown, ry, -right, -32768),
+    ])
+}
+
+#[cfg(windows)]
+pub use self::poll::XInputGamepad;
+
+#[cfg(windows)]
+mod poll {
+    use std::mem;
+    use std::time::{Duration, Instant};
+
+    use imgui::internal::RawCast;
+    use imgui::{sys, BackendFlags, ConfigFlags, Context, Io};
+    use windows::Win32::Foundation::ERROR_SUCCESS;
+    use windows::Win32::UI::Input::XboxController::{XInputGetState, XINPUT_STATE};
+
+    use super::map_gamepad;
+
+    /// Polling a disconnected controller is slow, so it is only retried this
+    /// often.
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Feeds the state of an XInput controller into imgui's gamepad
+    /// navigation.
+    pub struct XInputGamepad {
+        user_index: u32,
+        retry_at: Option<Instant>,
+    }
+
+    impl XInputGamepad {
+        /// Creates the poller for the controller `user_index`, which ranges
+        /// from 0 to 3, and enables gamepad navigation in `ctx`.
+        pub fn init(ctx: &mut Context, user_index: u32) -> Self {
+            ctx.io_mut().config_flags |= ConfigFlags::NAV_ENABLE_GAMEPAD;
+            XInputGamepad { user_index, retry_at: None }
+        }
+
+        /// Polls the controller and submits its state, call this before every
+        /// [`Context::new_frame`].
+        ///
+        /// [`BackendFlags::HAS_GAMEPAD`] is set while the controller is
+        /// connected.
+        pub fn prepare_frame(&mut self, io: &mut Io) {
+            io.backend_flags.remove(BackendFlags::HAS_GAMEPAD);
+            if self.retry_at.is_some_and(|at| Instant::now() < at) {
+                return;
+            }
+            let mut state: XINPUT_STATE = unsafe { mem::zeroed() };
+            if unsafe { XInputGetState(self.user_index, &mut state) } != ERROR_SUCCESS.0 {
+                self.retry_at = Some(Instant::now() + RECONNECT_INTERVAL);
+                return;
+            }
+            self.retry_at = None;
+            io.backend_flags.insert(BackendFlags::HAS_GAMEPAD);
+            for key in map_gamepad(&state.Gamepad) {
+                // SAFETY: Io is layout compatible with ImGuiIO
+                unsafe {
+                    sys::ImGuiIO_AddKeyAnalogEvent(
+                        io.raw_mut(),
+                        key.key as sys::ImGuiKey,
+                        key.down,
+                        key.value,
+                    )
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(keys: &[GamepadKey], key: Key) -> GamepadKey {
+        *keys.iter().find(|k| k.key == key).unwrap()
+    }
+
+    #[test]
+    fn maps_buttons() {
+        let gamepad = XINPUT_GAMEPAD {
+            wButtons: XINPUT_GAMEPAD_BUTTON_FLAGS(XINPUT_GAMEPAD_A.0 | XINPUT_GAMEPAD_DPAD_UP.0),
+            ..Default::default()
+        };
+        let keys: Vec<_> = map_gamepad(&gamepad).collect();
+        assert_eq!(
+            key(&keys, Key::GamepadFaceDown),
+            GamepadKey { key: Key::GamepadFaceDown, down: true, value: 1.0 }
+        );
+        assert!(key(&keys, Key::GamepadDpadUp).down);
+        assert_eq!(keys.iter().filter(|k| k.down).count(), 2);
+    }
+
+    #[test]
+    fn idle_controller_presses_nothing() {
+        assert!(map_gamepad(&XINPUT_GAMEPAD::default()).all(|k| !k.down && k.value == 0.0));
+    }
+
+    #[test]
+    fn ignores_stick_deadzone() {
+        let dz = XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE.0 as i16;
+        let gamepad = XINPUT_GAMEPAD { sThumbLX: dz - 1, sThumbLY: -dz, ..Default::default() };
+        assert!(map_gamepad(&gamepad).all(|k| !k.down && k.value == 0.0));
+    }
+
+    #[test]
+    fn maps_sticks_past_deadzone() {
+        let gamepad = XINPUT_GAMEPAD { sThumbLX: i16::MIN, sThumbRY: 20_000, ..Default::default() };
+        let keys: Vec<_> = map_gamepad(&gamepad).collect();
+        let left = key(&keys, Key::GamepadLStickLeft);
+        assert!(left.down && left.value == 1.0);
+        assert_eq!(key(&keys, Key::GamepadLStickRight).value, 0.0);
+        let up = key(&keys, Key::GamepadRStickUp);
+        let dz = XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE.0 as f32;
+        assert!(up.down && (up.value - (20_000.0 - dz) / (32767.0 - dz)).abs() < 1e-6);
+        assert!(!key(&keys, Key::GamepadRStickDown).down);
+    }
+
+    #[test]
+    fn triggers_need_threshold_to_count_as_pressed() {
+        // just past the deadzone the trigger reports a value but is not yet down
+        let gamepad = XINPUT_GAMEPAD { bLeftTrigger: 40, bRightTrigger: 255, ..Default::default() };
+        let keys: Vec<_> = map_gamepad(&gamepad).collect();
+        let l2 = key(&keys, Key::GamepadL2);
+        assert!(!l2.down && l2.value > 0.0);
+        assert_eq!(
+            key(&keys, Key::GamepadR2),
+            GamepadKey { key: Key::GamepadR2, down: true, value: 1.0 }
+        );
+    }
+}